@@ -0,0 +1,427 @@
+#[macro_use]
+extern crate serde_derive;
+
+use std::{
+    collections::BTreeMap,
+    io::Error,
+    path::Path,
+    process::{Command, ExitStatus},
+};
+
+pub use shell_link::Environment;
+
+/// How to start a program: the command, where it runs, its environment, and commands to run
+/// before it starts and after it exits
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Launch {
+    pub target: Vec<String>,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    // variables to set, or to unset when the value is null
+    #[serde(default)]
+    pub env: BTreeMap<String, Option<String>>,
+    #[serde(default)]
+    pub pre_launch: Vec<Vec<String>>,
+    #[serde(default)]
+    pub post_exit: Vec<Vec<String>>,
+    // join the target into a single command line for cmd.exe (or sh)
+    #[serde(default)]
+    pub shell: bool,
+}
+
+impl Launch {
+    pub fn validate(&self, env: &Environment) -> Result<(), String> {
+        if self.target.is_empty() {
+            return Err("empty target".into());
+        }
+
+        if let Some(cwd) = &self.cwd {
+            if !Path::new(&self.expand(env, cwd)).is_dir() {
+                return Err(format!("cwd is not a directory: {}", cwd));
+            }
+        }
+
+        for key in self.env.keys() {
+            if key.is_empty() || key.contains('=') {
+                return Err(format!("invalid environment variable name: {:?}", key));
+            }
+        }
+
+        let mut hooks = self.pre_launch.iter().chain(self.post_exit.iter());
+        if hooks.any(|hook| hook.is_empty()) {
+            return Err("empty hook command".into());
+        }
+
+        Ok(())
+    }
+
+    /// `env` with the variables the target sets and unsets
+    pub fn environment(&self, env: &Environment) -> Environment {
+        let mut env = env.clone();
+        for (key, value) in &self.env {
+            match value {
+                Some(value) => env.set(key, value),
+                None => env.remove(key),
+            }
+        }
+
+        env
+    }
+
+    /// Expand %VAR% in a path, with the variables the target sets
+    pub fn expand(&self, env: &Environment, value: &str) -> String {
+        self.environment(env).expand(value)
+    }
+
+    fn command(&self, env: &Environment, args: &[String], extra: &[String]) -> Command {
+        let env = self.environment(env);
+
+        let mut command = if self.shell {
+            let mut line = args.join(" ");
+            if !extra.is_empty() {
+                line = line + " " + &join_arguments(extra);
+            }
+
+            if cfg!(windows) {
+                let mut command = Command::new("cmd.exe");
+                command.arg("/C").arg(line);
+                command
+            } else {
+                let mut command = Command::new("sh");
+                command.arg("-c").arg(line);
+                command
+            }
+        } else {
+            let mut command = Command::new(env.expand(&args[0]));
+            command.args(args[1..].iter()).args(extra.iter());
+            command
+        };
+
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(env.expand(cwd));
+        }
+
+        command.env_clear();
+        command.envs(env.iter());
+        command
+    }
+
+    fn run_hooks(&self, env: &Environment, hooks: &[Vec<String>]) -> std::io::Result<()> {
+        for hook in hooks {
+            let status = self.command(env, hook, &[]).status()?;
+            check_status(hook, status)?;
+        }
+
+        Ok(())
+    }
+
+    /// Run the pre launch hooks, start the target, wait for it to exit and run the post exit
+    /// hooks. `env` is the environment the commands start with, `started` is given the
+    /// process id of the target once it's running and `exited` the same id when it's gone, since
+    /// it can belong to another process after that. Blocks until everything is done
+    pub fn run<S: FnOnce(u32), E: FnOnce(u32)>(
+        &self,
+        env: &Environment,
+        arguments: &[String],
+        started: S,
+        exited: E,
+    ) -> std::io::Result<ExitStatus> {
+        self.run_hooks(env, &self.pre_launch)?;
+
+        let mut child = self.command(env, &self.target, arguments).spawn()?;
+        started(child.id());
+
+        let status = child.wait();
+        exited(child.id());
+
+        let status = status?;
+        self.run_hooks(env, &self.post_exit)?;
+
+        Ok(status)
+    }
+}
+
+/// Join arguments into a command line, quoting them the way `CommandLineToArgvW` expects
+pub fn join_arguments<I, S>(args: I) -> String
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut out = String::new();
+
+    for arg in args {
+        let arg = arg.as_ref();
+        if !out.is_empty() {
+            out.push(' ');
+        }
+
+        if !arg.is_empty() && !arg.contains(&[' ', '\t', '"'][..]) {
+            out.push_str(arg);
+            continue;
+        }
+
+        out.push('"');
+        let mut backslashes = 0;
+        for c in arg.chars() {
+            match c {
+                '\\' => backslashes += 1,
+                '"' => {
+                    out.extend(std::iter::repeat_n('\\', backslashes * 2 + 1));
+                    backslashes = 0;
+                }
+                _ => {
+                    out.extend(std::iter::repeat_n('\\', backslashes));
+                    backslashes = 0;
+                }
+            }
+
+            if c != '\\' {
+                out.push(c);
+            }
+        }
+        out.extend(std::iter::repeat_n('\\', backslashes * 2));
+        out.push('"');
+    }
+
+    out
+}
+
+fn check_status(command: &[String], status: ExitStatus) -> std::io::Result<()> {
+    if status.success() {
+        Ok(())
+    } else {
+        let message = format!("{:?} failed: {}", command, status);
+        Err(Error::other(message))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::{cell::Cell, path::PathBuf};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("launch-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn parse(env: &Environment, yaml: &str) -> Launch {
+        let launch: Launch = serde_yaml::from_str(yaml).unwrap();
+        launch.validate(env).unwrap();
+        launch
+    }
+
+    // the variables of the test process are only read, tests set theirs on a copy
+    fn environment() -> Environment {
+        Environment::current()
+    }
+
+    #[test]
+    fn cwd_and_env() {
+        let dir = temp_dir("env");
+        let mut env = environment();
+        env.set("LAUNCH_UNSET", "still here");
+
+        let launch = parse(
+            &env,
+            &format!(
+                r#"
+target: [sh, -c, 'pwd > out.txt; echo "$GREETING ${{LAUNCH_UNSET-gone}}" >> out.txt']
+cwd: {}
+env:
+  GREETING: hello
+  LAUNCH_UNSET: ~
+"#,
+                dir.display()
+            ),
+        );
+
+        assert!(launch.run(&env, &[], |_| {}, |_| {}).unwrap().success());
+
+        let out = std::fs::read_to_string(dir.join("out.txt")).unwrap();
+        let expected = format!("{}\nhello gone\n", dir.canonicalize().unwrap().display());
+        assert_eq!(out, expected);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn hooks_run_in_order() {
+        let dir = temp_dir("hooks");
+        let env = environment();
+
+        // the target waits for the go file, so the log can be checked while it runs
+        let launch = parse(
+            &env,
+            &format!(
+                r#"
+target: [sh, -c, 'while [ ! -e go ]; do sleep 0.01; done; echo main "$@" >> log.txt', sh]
+cwd: {}
+pre_launch:
+  - [sh, -c, 'echo pre >> log.txt']
+post_exit:
+  - [sh, -c, 'echo post >> log.txt']
+"#,
+                dir.display()
+            ),
+        );
+
+        let mut pid = None;
+        let status = launch.run(
+            &env,
+            &["extra".into()],
+            |started| {
+                assert_eq!(
+                    std::fs::read_to_string(dir.join("log.txt")).unwrap(),
+                    "pre\n"
+                );
+                std::fs::write(dir.join("go"), "").unwrap();
+                pid = Some(started);
+            },
+            |_| {},
+        );
+
+        assert!(status.unwrap().success());
+        assert!(pid.is_some());
+
+        let out = std::fs::read_to_string(dir.join("log.txt")).unwrap();
+        assert_eq!(out, "pre\nmain extra\npost\n");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn failed_pre_launch_hook() {
+        let dir = temp_dir("failed");
+        let env = environment();
+
+        let launch = parse(
+            &env,
+            &format!(
+                r#"
+target: [sh, -c, 'echo main > out.txt']
+cwd: {}
+pre_launch:
+  - ['false']
+"#,
+                dir.display()
+            ),
+        );
+
+        let mut started = false;
+        assert!(launch.run(&env, &[], |_| started = true, |_| {}).is_err());
+        assert!(!started);
+        assert!(!dir.join("out.txt").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn shell_with_arguments() {
+        let dir = temp_dir("shell");
+        let env = environment();
+
+        let launch = parse(
+            &env,
+            &format!(
+                r#"
+target: ['echo args: > out.txt']
+cwd: {}
+shell: true
+"#,
+                dir.display()
+            ),
+        );
+
+        launch
+            .run(&env, &["-windowed".into()], |_| {}, |_| {})
+            .unwrap();
+
+        let out = std::fs::read_to_string(dir.join("out.txt")).unwrap();
+        assert_eq!(out, "args: -windowed\n");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn expanded_paths() {
+        let dir = temp_dir("expanded");
+        let mut env = environment();
+        env.set("LAUNCH_DIR", &dir.to_string_lossy());
+
+        let launch = parse(
+            &env,
+            r#"
+target: ['%LAUNCH_SHELL%', -c, 'pwd > out.txt']
+cwd: '%launch_dir%'
+env:
+  LAUNCH_SHELL: sh
+"#,
+        );
+
+        launch.run(&env, &[], |_| {}, |_| {}).unwrap();
+
+        let out = std::fs::read_to_string(dir.join("out.txt")).unwrap();
+        assert_eq!(out, format!("{}\n", dir.canonicalize().unwrap().display()));
+        assert_eq!(launch.expand(&env, "%LAUNCH_DIR%"), dir.to_string_lossy());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn relaunch_after_exit() {
+        let dir = temp_dir("relaunch");
+        let env = environment();
+
+        let launch = parse(
+            &env,
+            &format!(
+                r#"
+target: ['echo main >> out.txt']
+cwd: {}
+shell: true
+post_exit:
+  - ['echo post >> out.txt']
+"#,
+                dir.display()
+            ),
+        );
+
+        // what the launcher keeps to focus the target instead of starting it again
+        let pid = Cell::new(None);
+        for _ in 0..2 {
+            assert_eq!(pid.get(), None);
+
+            let status = launch.run(
+                &env,
+                &[],
+                |started| pid.set(Some(started)),
+                |exited| {
+                    assert_eq!(pid.get(), Some(exited));
+                    // the post exit hooks haven't run yet
+                    let out = std::fs::read_to_string(dir.join("out.txt")).unwrap();
+                    assert!(out.ends_with("main\n"), "{:?}", out);
+                    pid.set(None);
+                },
+            );
+            assert!(status.unwrap().success());
+        }
+
+        assert_eq!(pid.get(), None);
+        let out = std::fs::read_to_string(dir.join("out.txt")).unwrap();
+        assert_eq!(out, "main\npost\nmain\npost\n");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_targets() {
+        let invalid = [
+            "{ target: [] }",
+            "{ target: [b], cwd: /does/not/exist }",
+            "{ target: [b], env: { 'A=B': c } }",
+            "{ target: [b], post_exit: [[]] }",
+        ];
+
+        for yaml in &invalid {
+            let launch: Launch = serde_yaml::from_str(yaml).unwrap();
+            assert!(launch.validate(&environment()).is_err(), "{}", yaml);
+        }
+    }
+}
@@ -2,19 +2,27 @@ use std::ffi::OsStr;
 use std::path::Path;
 
 use winapi::{
-    shared::windef::HWND, um::winuser::keybd_event, um::winuser::SetFocus,
-    um::winuser::SetForegroundWindow,
+    shared::windef::HWND,
+    um::{
+        handleapi::CloseHandle,
+        processthreadsapi::GetProcessId,
+        shellapi::{ShellExecuteExW, SEE_MASK_NOCLOSEPROCESS, SHELLEXECUTEINFOW},
+        winreg::{RegGetValueW, HKEY_CURRENT_USER, RRF_RT_REG_DWORD},
+        winuser::{keybd_event, SetFocus, SetForegroundWindow},
+    },
 };
 
-mod read_dir_recursive;
-pub use read_dir_recursive::*;
-
 mod dllimport;
 pub use dllimport::*;
 
 mod ico;
 pub use ico::*;
 
+mod running;
+pub use running::*;
+
+pub use launch::join_arguments;
+
 pub fn focus_window(hwnd: HWND) {
     unsafe {
         keybd_event(0x12, 0, 1, 0);
@@ -26,6 +34,66 @@ pub fn focus_window(hwnd: HWND) {
     }
 }
 
+/// Open a file through the shell, returns the process id if a new process was started.
+/// `show` is the SW_* value for the window
+pub fn shell_execute<P: ToWide>(
+    file: P,
+    parameters: Option<&str>,
+    directory: Option<&Path>,
+    show: i32,
+) -> Option<u32> {
+    let op = "open".to_wide();
+    let file = file.to_wide();
+    let parameters = parameters.map(|p| p.to_wide());
+    let directory = directory.map(|d| d.to_wide());
+
+    unsafe {
+        let mut info: SHELLEXECUTEINFOW = std::mem::zeroed();
+        info.cbSize = std::mem::size_of::<SHELLEXECUTEINFOW>() as u32;
+        info.fMask = SEE_MASK_NOCLOSEPROCESS;
+        info.lpVerb = op.as_ptr();
+        info.lpFile = file.as_ptr();
+        info.lpParameters = parameters.as_ref().map_or(std::ptr::null(), |p| p.as_ptr());
+        info.lpDirectory = directory.as_ref().map_or(std::ptr::null(), |d| d.as_ptr());
+        info.nShow = show;
+
+        if ShellExecuteExW(&mut info) == 0 || info.hProcess.is_null() {
+            return None;
+        }
+
+        let pid = GetProcessId(info.hProcess);
+        CloseHandle(info.hProcess);
+
+        Some(pid)
+    }
+}
+
+/// Read a DWORD value of a key under HKEY_CURRENT_USER
+pub fn read_user_dword(key: &str, value: &str) -> Option<u32> {
+    let key = key.to_wide();
+    let value = value.to_wide();
+
+    unsafe {
+        let mut data = 0u32;
+        let mut size = std::mem::size_of::<u32>() as u32;
+
+        let status = RegGetValueW(
+            HKEY_CURRENT_USER,
+            key.as_ptr(),
+            value.as_ptr(),
+            RRF_RT_REG_DWORD,
+            std::ptr::null_mut(),
+            &mut data as *mut u32 as *mut _,
+            &mut size,
+        );
+
+        match status {
+            0 => Some(data),
+            _ => None,
+        }
+    }
+}
+
 pub trait ToWide {
     fn to_wide(self) -> Vec<u16>;
 }
@@ -0,0 +1,378 @@
+use std::{
+    ffi::OsString,
+    os::windows::ffi::OsStringExt,
+    path::{Path, PathBuf},
+};
+
+use winapi::{
+    shared::minwindef::LPARAM,
+    shared::windef::HWND,
+    um::{
+        handleapi::{CloseHandle, INVALID_HANDLE_VALUE},
+        processthreadsapi::OpenProcess,
+        tlhelp32::{
+            CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W,
+            TH32CS_SNAPPROCESS,
+        },
+        winbase::QueryFullProcessImageNameW,
+        winnt::PROCESS_QUERY_LIMITED_INFORMATION,
+        winuser::{EnumWindows, GetWindow, GetWindowThreadProcessId, IsWindowVisible, GW_OWNER},
+    },
+};
+
+use super::focus_window;
+
+#[derive(Debug, Clone)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub exe: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WindowInfo {
+    pub hwnd: usize,
+    pub pid: u32,
+    pub visible: bool,
+    // (hwnd, process id) of the owner window, if any
+    pub owner: Option<(usize, u32)>,
+}
+
+pub trait ProcessTable {
+    fn processes(&self) -> Vec<ProcessInfo>;
+    fn windows(&self) -> Vec<WindowInfo>;
+}
+
+/// Describes how to recognize a running instance of a target
+#[derive(Debug, Clone, Default)]
+pub struct RunningMatch {
+    pub pid: Option<u32>,
+    pub exe: Option<PathBuf>,
+    pub dir: Option<PathBuf>,
+}
+
+fn normalize(path: &Path) -> String {
+    path.to_string_lossy().to_lowercase().replace('/', "\\")
+}
+
+impl RunningMatch {
+    pub fn is_empty(&self) -> bool {
+        self.pid.is_none() && self.exe.is_none() && self.dir.is_none()
+    }
+
+    pub fn matches(&self, process: &ProcessInfo) -> bool {
+        let exe = process.exe.as_ref().map(|p| normalize(p));
+
+        if let (Some(expected), Some(actual)) = (&self.exe, &exe) {
+            if normalize(expected) == *actual {
+                return true;
+            }
+        }
+
+        if let (Some(dir), Some(actual)) = (&self.dir, &exe) {
+            let dir = normalize(dir);
+            let dir = dir.trim_end_matches('\\');
+            if actual.starts_with(dir) && actual[dir.len()..].starts_with('\\') {
+                return true;
+            }
+        }
+
+        if let Some(pid) = self.pid {
+            // process ids get reused, so don't trust one that now belongs to a different exe
+            let same_exe = match (&self.exe, &exe) {
+                (Some(expected), Some(actual)) => normalize(expected) == *actual,
+                _ => true,
+            };
+
+            if pid == process.pid && same_exe {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+/// Find the window that should be focused for a running instance, if there is one
+pub fn find_window<T: ProcessTable>(table: &T, query: &RunningMatch) -> Option<usize> {
+    if query.is_empty() {
+        return None;
+    }
+
+    let pids: Vec<_> = table
+        .processes()
+        .into_iter()
+        .filter(|p| query.matches(p))
+        .map(|p| p.pid)
+        .collect();
+
+    if pids.is_empty() {
+        return None;
+    }
+
+    table
+        .windows()
+        .into_iter()
+        .filter(|w| w.visible && pids.contains(&w.pid))
+        .find_map(|w| match w.owner {
+            None => Some(w.hwnd),
+            // the owner is found on its own when it's from the same process
+            Some((_, owner_pid)) if owner_pid == w.pid => None,
+            // only follow owners that belong to the target, never focus another program
+            Some((owner, owner_pid)) if pids.contains(&owner_pid) => Some(owner),
+            Some(_) => Some(w.hwnd),
+        })
+}
+
+/// Focus the window of a running instance, returns false if nothing matched
+pub fn focus_running(query: &RunningMatch) -> bool {
+    match find_window(&SystemProcessTable, query) {
+        Some(hwnd) => {
+            focus_window(hwnd as HWND);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Resolve a command name the way `CreateProcess` would, using PATH and PATHEXT
+pub fn which<P: AsRef<Path>>(name: P) -> Option<PathBuf> {
+    let name = name.as_ref();
+    if name.components().count() > 1 {
+        return Some(name.to_owned());
+    }
+
+    let paths = std::env::var_os("PATH")?;
+    let exts = std::env::var("PATHEXT").unwrap_or(".COM;.EXE;.BAT;.CMD".into());
+
+    for dir in std::env::split_paths(&paths) {
+        let candidate = dir.join(name);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+
+        for ext in exts.split(';').filter(|e| e.len() > 0) {
+            let mut with_ext = candidate.clone().into_os_string();
+            with_ext.push(ext);
+
+            let with_ext = PathBuf::from(with_ext);
+            if with_ext.is_file() {
+                return Some(with_ext);
+            }
+        }
+    }
+
+    None
+}
+
+pub struct SystemProcessTable;
+
+impl ProcessTable for SystemProcessTable {
+    fn processes(&self) -> Vec<ProcessInfo> {
+        let mut out = vec![];
+
+        unsafe {
+            let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0);
+            if snapshot == INVALID_HANDLE_VALUE {
+                return out;
+            }
+
+            let mut entry: PROCESSENTRY32W = std::mem::zeroed();
+            entry.dwSize = std::mem::size_of::<PROCESSENTRY32W>() as u32;
+
+            let mut more = Process32FirstW(snapshot, &mut entry);
+            while more != 0 {
+                let pid = entry.th32ProcessID;
+                let exe = process_image_name(pid);
+                out.push(ProcessInfo { pid, exe });
+
+                more = Process32NextW(snapshot, &mut entry);
+            }
+
+            CloseHandle(snapshot);
+        }
+
+        out
+    }
+
+    fn windows(&self) -> Vec<WindowInfo> {
+        unsafe extern "system" fn enum_windows_helper(win: HWND, l: LPARAM) -> i32 {
+            let out = &mut *(l as *mut Vec<WindowInfo>);
+
+            let mut pid = 0;
+            GetWindowThreadProcessId(win, &mut pid);
+
+            let parent = GetWindow(win, GW_OWNER);
+            let owner = if parent.is_null() {
+                None
+            } else {
+                let mut parent_pid = 0;
+                GetWindowThreadProcessId(parent, &mut parent_pid);
+                Some((parent as usize, parent_pid))
+            };
+
+            out.push(WindowInfo {
+                hwnd: win as usize,
+                pid,
+                visible: IsWindowVisible(win) != 0,
+                owner,
+            });
+
+            1
+        }
+
+        let mut out: Vec<WindowInfo> = vec![];
+        unsafe {
+            EnumWindows(Some(enum_windows_helper), &mut out as *mut _ as LPARAM);
+        }
+
+        out
+    }
+}
+
+/// The full path of the executable a process is running
+pub fn process_image_name(pid: u32) -> Option<PathBuf> {
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if handle.is_null() {
+            return None;
+        }
+
+        let mut value = [0; 1024];
+        let mut len = value.len() as u32;
+        let result = QueryFullProcessImageNameW(handle, 0, value.as_mut_ptr(), &mut len);
+        CloseHandle(handle);
+
+        if result == 0 {
+            None
+        } else {
+            Some(OsString::from_wide(&value[..len as usize]).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FakeTable {
+        processes: Vec<ProcessInfo>,
+        windows: Vec<WindowInfo>,
+    }
+
+    impl ProcessTable for FakeTable {
+        fn processes(&self) -> Vec<ProcessInfo> {
+            self.processes.clone()
+        }
+
+        fn windows(&self) -> Vec<WindowInfo> {
+            self.windows.clone()
+        }
+    }
+
+    fn process(pid: u32, exe: &str) -> ProcessInfo {
+        let exe = Some(PathBuf::from(exe));
+        ProcessInfo { pid, exe }
+    }
+
+    fn window(hwnd: usize, pid: u32, owner: Option<(usize, u32)>) -> WindowInfo {
+        let visible = true;
+        WindowInfo {
+            hwnd,
+            pid,
+            visible,
+            owner,
+        }
+    }
+
+    fn table() -> FakeTable {
+        FakeTable {
+            processes: vec![
+                process(10, r"C:\Windows\explorer.exe"),
+                process(20, r"D:\Games\Celeste\Celeste.exe"),
+                process(30, r"D:\Games\Hades\x64\Hades.exe"),
+                process(40, r"C:\Tools\helper.exe"),
+                process(50, r"D:\Games\Hades\launcher.exe"),
+            ],
+            windows: vec![
+                window(100, 10, None),
+                window(200, 20, None),
+                window(300, 30, Some((301, 30))),
+                window(301, 30, None),
+                window(400, 40, Some((100, 10))),
+                window(500, 50, Some((301, 30))),
+            ],
+        }
+    }
+
+    #[test]
+    fn match_by_exe() {
+        let query = RunningMatch {
+            exe: Some(r"d:\games\celeste\CELESTE.exe".into()),
+            ..Default::default()
+        };
+
+        assert_eq!(find_window(&table(), &query), Some(200));
+    }
+
+    #[test]
+    fn match_by_dir() {
+        let query = RunningMatch {
+            dir: Some(r"D:\Games\Hades\".into()),
+            ..Default::default()
+        };
+
+        assert_eq!(find_window(&table(), &query), Some(301));
+
+        let query = RunningMatch {
+            dir: Some(r"D:\Games\Had".into()),
+            ..Default::default()
+        };
+
+        assert_eq!(find_window(&table(), &query), None);
+    }
+
+    #[test]
+    fn match_by_pid() {
+        let query = RunningMatch {
+            pid: Some(40),
+            ..Default::default()
+        };
+
+        // owned by a window of a process that isn't the target, which is left alone
+        assert_eq!(find_window(&table(), &query), Some(400));
+
+        let query = RunningMatch {
+            pid: Some(50),
+            dir: Some(r"D:\Games\Hades".into()),
+            ..Default::default()
+        };
+
+        // owned by another process of the target, so focus the owner
+        let mut launcher_first = table();
+        launcher_first.windows.rotate_right(1);
+        assert_eq!(find_window(&launcher_first, &query), Some(301));
+
+        let query = RunningMatch {
+            pid: Some(40),
+            exe: Some(r"C:\Tools\other.exe".into()),
+            ..Default::default()
+        };
+
+        // the recorded process id has been reused by a different program
+        assert_eq!(find_window(&table(), &query), None);
+    }
+
+    #[test]
+    fn ignore_hidden_windows() {
+        let mut table = table();
+        table.windows[1].visible = false;
+
+        let query = RunningMatch {
+            exe: Some(r"D:\Games\Celeste\Celeste.exe".into()),
+            ..Default::default()
+        };
+
+        assert_eq!(find_window(&table, &query), None);
+        assert_eq!(find_window(&table, &Default::default()), None);
+    }
+}
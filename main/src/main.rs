@@ -1,6 +1,5 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-extern crate bitflags;
 extern crate cef;
 extern crate image;
 extern crate lazy_static;
@@ -78,6 +77,19 @@ fn main() {
         std::fs::remove_file(x).unwrap();
     }
 
+    // game-launcher.exe --export-steam-shortcuts <name>...
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("--export-steam-shortcuts") {
+        render::search::export_steam_shortcuts(&args[1..]);
+        return;
+    }
+
+    // game-launcher.exe --export-shortcuts <dir> <name>...
+    if args.first().map(String::as_str) == Some("--export-shortcuts") && args.len() > 1 {
+        render::search::export_shortcuts(Path::new(&args[1]), &args[2..]);
+        return;
+    }
+
     let hinstance = unsafe { GetModuleHandleA(std::ptr::null()) };
     let main_args = CefMainArgs::new(hinstance as _);
 
@@ -1,5 +1,6 @@
 use cef::{CefV8Context, CefV8Value, V8ArrayBufferReleaseCallback};
 
+#[derive(Clone)]
 pub struct CefImageFactory {
     factory: CefV8Value,
 }
@@ -7,7 +7,10 @@ use cef::{
 
 pub mod search;
 use image::ImageOutputFormat;
-use search::{AnyTarget, Index, Match, Provider, SearchProvider};
+use search::{
+    split_query, split_tags, AnyTarget, Artwork, Index, LaunchOptions, Match, Provider,
+    SearchProvider,
+};
 
 pub mod cef_image;
 use cef_image::CefImageFactory;
@@ -47,6 +50,9 @@ impl RenderProcessHandler for MyRenderProcessHandler {
             .map(|index| make_cef_target(rc.clone(), &assets, index))
             .collect();
 
+        // launch closures keep state between launches, so create them once per target
+        let launchers: Rc<Vec<_>> = Rc::new(rc.index().iter().map(|i| rc.launch(i)).collect());
+
         crate::mark!("created object cache: {:?}", _mark);
 
         let root_object = CefV8Value::create_object(None, None).unwrap();
@@ -63,14 +69,18 @@ impl RenderProcessHandler for MyRenderProcessHandler {
 
         let key = "search";
         let search_fn = move |query: String| {
+            let (text, arguments) = split_query(&query);
+            let (text, tags) = split_tags(text);
+            let suffix = &query[text.len()..];
+
             let search = rc.borrow();
-            let matches = search.search(&query.to_lowercase());
+            let matches = search.search(&text.to_lowercase(), &tags);
 
             let limit = 7.min(matches.len());
             let display = matches
                 .into_iter()
                 .take(limit)
-                .map(|m| make_cef_match(&objects, &query, m));
+                .map(|m| make_cef_match(&objects, &launchers, text, suffix, &arguments, m));
 
             v8::v8_array(display)
         };
@@ -137,6 +147,10 @@ pub fn make_cef_target(
     let value = rc.details(&index);
     object.set_value_bykey(Some(&key.into()), value, CefV8Propertyattribute::NONE);
 
+    let key = "installed";
+    let value = rc.is_installed(&index);
+    object.set_value_bykey(Some(&key.into()), value, CefV8Propertyattribute::NONE);
+
     let key = "display_icon";
     let display_icon = rc.display_icon(&index);
     let value: CefV8Value = match display_icon {
@@ -149,15 +163,43 @@ pub fn make_cef_target(
     };
     object.set_value_bykey(Some(&key.into()), value, CefV8Propertyattribute::NONE);
 
-    let key = "launch";
-    let launch = rc.launch(&index);
-    let value = v8::v8_function0(key, move || launch());
+    // artwork('capsule' | 'header' | 'hero'), loaded when asked for since the images are large
+    let key = "artwork";
+    let assets = assets.clone();
+    let artwork_fn = move |kind: String| -> CefV8Value {
+        let path = Artwork::from_name(&kind).and_then(|kind| rc.artwork(&index, kind));
+        let path = match path {
+            Some(path) => path,
+            None => return ().into(),
+        };
+
+        let data = crate::attempt!(("load artwork {:?}", path), std::fs::read(&path)?);
+        match data {
+            None => ().into(),
+            Some(mut data) => {
+                let mime = match path.extension().and_then(|e| e.to_str()) {
+                    Some("png") => "image/png",
+                    _ => "image/jpeg",
+                };
+
+                assets.create_asset(mime, &mut data)
+            }
+        }
+    };
+    let value = v8::v8_function1(key, artwork_fn);
     object.set_value_bykey(Some(&key.into()), value, CefV8Propertyattribute::NONE);
 
     object
 }
 
-pub fn make_cef_match(targets: &[CefV8Value], query: &str, m: Match<usize>) -> CefV8Value {
+pub fn make_cef_match(
+    targets: &[CefV8Value],
+    launchers: &Rc<Vec<Box<dyn Fn(&LaunchOptions)>>>,
+    query: &str,
+    suffix: &str,
+    arguments: &[String],
+    m: Match<usize>,
+) -> CefV8Value {
     let object = CefV8Value::create_object(None, None).unwrap();
 
     let key = "key";
@@ -172,9 +214,28 @@ pub fn make_cef_match(targets: &[CefV8Value], query: &str, m: Match<usize>) -> C
     let value = m.index + query.len();
     object.set_value_bykey(Some(&key.into()), value, CefV8Propertyattribute::NONE);
 
+    let key = "suffix";
+    let value = suffix;
+    object.set_value_bykey(Some(&key.into()), value, CefV8Propertyattribute::NONE);
+
     let key = "target";
     let value = targets[m.value].clone();
     object.set_value_bykey(Some(&key.into()), value, CefV8Propertyattribute::NONE);
 
+    let key = "launch";
+    let launchers = launchers.clone();
+    let arguments = arguments.to_vec();
+    let index = m.value;
+    let launch_fn = move |new_instance: i32| {
+        let options = LaunchOptions {
+            new_instance: new_instance != 0,
+            arguments: arguments.clone(),
+        };
+
+        launchers[index](&options)
+    };
+    let value = v8::v8_function1(key, launch_fn);
+    object.set_value_bykey(Some(&key.into()), value, CefV8Propertyattribute::NONE);
+
     object
 }
@@ -5,21 +5,18 @@ use std::{
     path::Path,
     process::Command,
     str::FromStr,
+    sync::{Arc, Mutex},
 };
 
 use com::{interfaces::IUnknown, runtime::create_instance, sys::GUID};
 use image::{imageops::FilterType, DynamicImage};
 use lazy_static::lazy_static;
-use winapi::{
-    shared::minwindef::DWORD, shared::minwindef::LPARAM, shared::windef::HWND,
-    shared::winerror::HRESULT, um::winuser::EnumWindows, um::winuser::GetWindow,
-    um::winuser::GetWindowThreadProcessId, um::winuser::GW_OWNER,
-};
+use winapi::{shared::minwindef::DWORD, shared::winerror::HRESULT};
 
-use super::SearchProvider;
+use super::{steam::ExportShortcut, LaunchOptions, SearchProvider};
 use crate::{
     bindings::windows::management::deployment::PackageManager,
-    common::{Dll, ToWide},
+    common::{focus_running, join_arguments, process_image_name, Dll, RunningMatch, ToWide},
 };
 
 #[com_interface("2e941141-7f97-4756-ba1d-9decde894a3d")]
@@ -385,6 +382,24 @@ impl AppxProvider {
     }
 }
 
+impl AppxTarget {
+    /// Describe the target as a shortcut, explorer activates packaged apps by their id
+    pub fn shortcut(&self) -> ExportShortcut {
+        let windir = std::env::var("windir").unwrap_or_else(|_| r"C:\Windows".into());
+
+        ExportShortcut {
+            name: self.name.clone(),
+            exe: Path::new(&windir)
+                .join("explorer.exe")
+                .to_string_lossy()
+                .into_owned(),
+            launch_options: format!(r"shell:AppsFolder\{}", self.launch_id),
+            app_user_model_id: self.launch_id.clone(),
+            ..Default::default()
+        }
+    }
+}
+
 impl SearchProvider<AppxTarget> for AppxProvider {
     fn index(&self) -> Vec<AppxTarget> {
         let raw = list_start_apps();
@@ -407,10 +422,13 @@ impl SearchProvider<AppxTarget> for AppxProvider {
         vec![entry.name.clone()]
     }
 
-    fn launch(&self, entry: &AppxTarget) -> Box<dyn Fn()> {
+    fn launch(&self, entry: &AppxTarget) -> Box<dyn Fn(&LaunchOptions)> {
         let launch_id = entry.launch_id.clone();
+        // the process started last and its exe, since the id alone can be reused by another
+        // program once the app exits
+        let started = Arc::new(Mutex::new(None));
 
-        Box::new(move || {
+        Box::new(move |options| {
             const CLSID: GUID = GUID {
                 data1: 0x45BA127D,
                 data2: 0x10A8,
@@ -418,7 +436,27 @@ impl SearchProvider<AppxTarget> for AppxProvider {
                 data4: [0x8A, 0xB7, 0x56, 0xEA, 0x90, 0x78, 0x94, 0x3C],
             };
 
+            if options.focus_running() {
+                let last = started.lock().unwrap().clone();
+                if let Some((pid, exe)) = last {
+                    let running = RunningMatch {
+                        pid: Some(pid),
+                        exe: Some(exe),
+                        ..Default::default()
+                    };
+
+                    if focus_running(&running) {
+                        return;
+                    }
+                }
+            }
+
             let raw = launch_id.to_wide();
+            let arguments = match options.arguments.len() {
+                0 => None,
+                _ => Some(join_arguments(&options.arguments).to_wide()),
+            };
+            let started = started.clone();
 
             unsafe {
                 std::thread::spawn(move || {
@@ -426,37 +464,22 @@ impl SearchProvider<AppxTarget> for AppxProvider {
                         .expect("failed to instantiate ApplicationActivationManager");
 
                     let mut process_id = 0;
-                    am.activate_application(raw.as_ptr(), std::ptr::null(), 0, &mut process_id);
+                    let arguments = arguments.as_ref().map_or(std::ptr::null(), |a| a.as_ptr());
+                    am.activate_application(raw.as_ptr(), arguments, 0, &mut process_id);
+
+                    // an app that has already exited can't be matched safely
+                    let exe = process_image_name(process_id);
+                    *started.lock().unwrap() = exe.clone().map(|exe| (process_id, exe));
 
                     std::thread::sleep(std::time::Duration::from_millis(200));
 
-                    EnumWindows(Some(enum_windows_helper), process_id as isize);
+                    focus_running(&RunningMatch {
+                        pid: Some(process_id),
+                        exe,
+                        ..Default::default()
+                    });
                 });
             }
-
-            unsafe extern "system" fn enum_windows_helper(win: HWND, l: LPARAM) -> i32 {
-                let original = l as u32;
-
-                let mut process_id = 0;
-                GetWindowThreadProcessId(win, &mut process_id);
-
-                if original == process_id {
-                    let parent = GetWindow(win, GW_OWNER);
-                    if parent.is_null() {
-                        crate::common::focus_window(win);
-                        return 0;
-                    }
-
-                    let mut parent_process_id = 0;
-                    GetWindowThreadProcessId(parent, &mut parent_process_id);
-                    if parent_process_id != process_id {
-                        crate::common::focus_window(parent);
-                        return 0;
-                    }
-                }
-
-                1
-            }
         })
     }
 
@@ -1,6 +1,18 @@
-use std::{fs::File, io::prelude::*, process::Command};
+use std::{
+    fs::File,
+    io::prelude::*,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
 
-use super::{appx::AppxConfig, start_menu::StartMenuConfig, steam::SteamConfig, SearchProvider};
+use super::{
+    appx::AppxConfig,
+    start_menu::StartMenuConfig,
+    steam::{ExportShortcut, SteamConfig},
+    LaunchOptions, SearchProvider,
+};
+use crate::common::{focus_running, join_arguments, which, RunningMatch};
+use launch::{Environment, Launch};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SearchConfig {
@@ -29,15 +41,89 @@ impl SearchConfig {
             serde_yaml::from_slice(&content)?
         });
 
-        raw.unwrap_or_default()
+        let mut config: SearchConfig = raw.unwrap_or_default();
+        config.custom.retain(|target| match target.validate() {
+            Ok(()) => true,
+            Err(e) => {
+                crate::log!("invalid custom target {:?}: {}", target.names, e);
+                false
+            }
+        });
+
+        config
     }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Hash)]
 pub struct ManualTarget {
     names: Vec<String>,
-    target: Vec<String>,
     icon: Option<String>,
+    // target, cwd, env, hooks and shell
+    #[serde(flatten)]
+    launch: Launch,
+}
+
+impl ManualTarget {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.names.is_empty() {
+            return Err("no names".into());
+        }
+
+        self.launch.validate(&Environment::current())
+    }
+
+    /// Expand %VAR% in a path, with the variables the target sets
+    fn expand(&self, value: &str) -> String {
+        self.launch.expand(&Environment::current(), value)
+    }
+
+    /// Describe the target as a steam shortcut, hooks and environment variables are not kept
+    pub fn steam_shortcut(&self) -> ExportShortcut {
+        let launch = &self.launch;
+        if launch.env.len() > 0 || launch.pre_launch.len() > 0 || launch.post_exit.len() > 0 {
+            crate::log!(
+                "{:?}: steam shortcuts can't run hooks or set the environment",
+                self.names
+            );
+        }
+
+        let (exe, launch_options) = if launch.shell {
+            let shell = if cfg!(windows) { "cmd.exe" } else { "sh" };
+            let flag = if cfg!(windows) { "/C" } else { "-c" };
+            let exe = which(shell).unwrap_or_else(|| PathBuf::from(shell));
+            (exe, format!("{} {}", flag, launch.target.join(" ")))
+        } else {
+            let target = self.expand(&launch.target[0]);
+            let exe = which(&target).unwrap_or_else(|| PathBuf::from(target));
+            (exe, join_arguments(&launch.target[1..]))
+        };
+
+        let start_dir = match &launch.cwd {
+            Some(cwd) => PathBuf::from(self.expand(cwd)),
+            None => exe.parent().map(Path::to_owned).unwrap_or_default(),
+        };
+
+        // steam resolves relative icons against its own directory. canonicalize would give a
+        // \\?\ path, which steam doesn't understand
+        let icon = self
+            .icon
+            .as_ref()
+            .map(|icon| self.expand(icon))
+            .map(|icon| match std::path::absolute(&icon) {
+                Ok(path) => path.to_string_lossy().into_owned(),
+                Err(_) => icon,
+            });
+
+        ExportShortcut {
+            name: self.names[0].clone(),
+            exe: exe.to_string_lossy().into_owned(),
+            start_dir: start_dir.to_string_lossy().into_owned(),
+            launch_options,
+            icon: icon.unwrap_or_default(),
+            shortcut_path: String::new(),
+            app_user_model_id: String::new(),
+        }
+    }
 }
 
 impl SearchProvider<ManualTarget> for SearchConfig {
@@ -49,25 +135,63 @@ impl SearchProvider<ManualTarget> for SearchConfig {
         entry.names.clone()
     }
 
-    fn launch(&self, entry: &ManualTarget) -> Box<dyn Fn()> {
-        let target = entry.target.clone();
+    fn launch(&self, entry: &ManualTarget) -> Box<dyn Fn(&LaunchOptions)> {
+        let entry = entry.clone();
+        // set by the thread that runs the target while it's running
+        let pid = Arc::new(Mutex::new(None));
+
+        Box::new(move |options| {
+            if options.focus_running() {
+                let running = RunningMatch {
+                    pid: *pid.lock().unwrap(),
+                    exe: if entry.launch.shell {
+                        None
+                    } else {
+                        which(entry.expand(&entry.launch.target[0]))
+                    },
+                    ..Default::default()
+                };
+
+                if focus_running(&running) {
+                    return;
+                }
+            }
+
+            // the hooks can take a while, and the target is waited on for the post exit ones
+            let names = entry.names.clone();
+            let launch = entry.launch.clone();
+            let arguments = options.arguments.clone();
+            let pid = pid.clone();
 
-        Box::new(move || {
-            Command::new(&target[0])
-                .args(target[1..].iter())
-                .spawn()
-                .expect("spawn process");
+            std::thread::spawn(move || {
+                crate::attempt!(("launch {:?}", names), {
+                    let env = Environment::current();
+                    launch.run(
+                        &env,
+                        &arguments,
+                        |launched| *pid.lock().unwrap() = Some(launched),
+                        // the id can be given to another program once the target exits,
+                        // unless a newer instance took its place
+                        |exited| {
+                            let mut pid = pid.lock().unwrap();
+                            if *pid == Some(exited) {
+                                *pid = None;
+                            }
+                        },
+                    )?
+                });
+            });
         })
     }
 
     fn details(&self, entry: &ManualTarget) -> String {
-        entry.target[0].clone()
+        entry.launch.target[0].clone()
     }
 
     fn display_icon(&self, entry: &ManualTarget) -> Option<image::DynamicImage> {
         let icon_path = match &entry.icon {
-            Some(name) => name,
-            None => &entry.names[0],
+            Some(name) => entry.expand(name),
+            None => entry.names[0].clone(),
         };
 
         crate::attempt!(("open manual icon {}", icon_path), {
@@ -1,8 +1,16 @@
 use std::{
-    cell::RefCell, cmp::Ordering, fmt::Debug, fs::File, io::BufReader, path::PathBuf, rc::Rc,
+    cell::RefCell,
+    cmp::Ordering,
+    collections::HashSet,
+    fmt::Debug,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    rc::Rc,
 };
 
 use image::{imageops::FilterType, DynamicImage};
+use shell_link::ShellLink;
 
 mod config;
 use config::{ManualTarget, SearchConfig};
@@ -15,17 +23,152 @@ use serde::{de::DeserializeOwned, Serialize};
 use start_menu::{StartMenuProvider, StartMenuTarget};
 
 mod steam;
-use steam::{SteamProvider, SteamTarget};
+use steam::{
+    ExportShortcut, SteamProvider, SteamShortcutProvider, SteamShortcutTarget, SteamTarget,
+};
+
+pub type MatchScore = (usize, usize, bool, u64);
+
+#[derive(Debug, Clone, Default)]
+pub struct LaunchOptions {
+    // start another copy even if the target is already running
+    pub new_instance: bool,
+    // extra arguments typed after the query, see `split_query`
+    pub arguments: Vec<String>,
+}
+
+impl LaunchOptions {
+    /// Whether to focus a running instance instead of launching. Extra arguments are meant
+    /// for a new process, focusing the old one would drop them
+    pub fn focus_running(&self) -> bool {
+        !self.new_instance && self.arguments.is_empty()
+    }
+}
+
+/// Split a query like `blender -- --factory-startup` into the search text and the launch arguments
+pub fn split_query(query: &str) -> (&str, Vec<String>) {
+    let mut offset = 0;
+    while let Some(i) = query[offset..].find("--") {
+        let start = offset + i;
+        let end = start + 2;
+
+        let before = query[..start].chars().next_back();
+        let after = query[end..].chars().next();
+
+        if before.map_or(true, char::is_whitespace) && after.map_or(true, char::is_whitespace) {
+            return (query[..start].trim_end(), split_arguments(&query[end..]));
+        }
+
+        offset = end;
+    }
+
+    (query, vec![])
+}
+
+/// Split arguments on whitespace, double quotes group an argument that contains spaces
+pub fn split_arguments(src: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut current: Option<String> = None;
+    let mut quoted = false;
+
+    for c in src.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !quoted => {
+                args.extend(current.take());
+            }
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    args.extend(current);
+    args
+}
+
+/// Split `#tag` filters off the end of the search text, `portal #coop` looks for portal
+/// among the targets tagged with something like co-op
+pub fn split_tags(text: &str) -> (&str, Vec<String>) {
+    let mut text = text.trim_end();
+    let mut tags = vec![];
+
+    while let Some(word) = text.split_whitespace().next_back() {
+        let tag = normalize_tag(word);
+        if !word.starts_with('#') || tag.is_empty() {
+            break;
+        }
 
-pub type MatchScore = (usize, usize, u64);
+        tags.insert(0, tag);
+        text = text[..text.len() - word.len()].trim_end();
+    }
+
+    (text, tags)
+}
+
+// tags are compared on their letters and digits only, so #coop finds co-op
+fn normalize_tag(tag: &str) -> String {
+    tag.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Larger images of a target, for views that show more than the icon
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Artwork {
+    // portrait cover art
+    Capsule,
+    // landscape banner
+    Header,
+    // wide background image
+    Hero,
+}
+
+impl Artwork {
+    pub fn from_name(name: &str) -> Option<Artwork> {
+        match name {
+            "capsule" => Some(Artwork::Capsule),
+            "header" => Some(Artwork::Header),
+            "hero" => Some(Artwork::Hero),
+            _ => None,
+        }
+    }
+}
 
 pub trait SearchProvider<K> {
     fn index(&self) -> Vec<K>;
 
     fn keys(&self, target: &K) -> Vec<String>;
-    fn launch(&self, target: &K) -> Box<dyn Fn()>;
+    fn launch(&self, target: &K) -> Box<dyn Fn(&LaunchOptions)>;
     fn details(&self, target: &K) -> String;
     fn display_icon(&self, target: &K) -> Option<DynamicImage>;
+
+    /// The launch counter for a target that is new to the index
+    fn seed_counter(&self, _target: &K) -> u64 {
+        0
+    }
+
+    fn artwork(&self, _target: &K, _kind: Artwork) -> Option<PathBuf> {
+        None
+    }
+
+    /// False for targets that are listed but have to be installed before they can run
+    fn is_installed(&self, _target: &K) -> bool {
+        true
+    }
+
+    /// Names the target can be filtered by with `#name`, like the collections it is in
+    fn tags(&self, _target: &K) -> Vec<String> {
+        vec![]
+    }
+
+    /// True to rank the target below others that match the query as well,
+    /// e.g. because it can't be started right now
+    fn demoted(&self, _target: &K) -> bool {
+        false
+    }
 }
 
 pub struct Match<'a, T> {
@@ -44,6 +187,8 @@ struct IndexEntryMeta {
 struct IndexEntry<T> {
     target: T,
     keys: Vec<(String, String)>,
+    // normalized, along with each of their words
+    tags: Vec<String>,
     meta: IndexEntryMeta,
 }
 
@@ -61,10 +206,27 @@ impl<T> IndexEntry<T> {
             })
             .collect();
 
-        IndexEntry { keys, meta, target }
+        let mut tags = vec![];
+        for tag in provider.tags(&target) {
+            tags.push(normalize_tag(&tag));
+            tags.extend(tag.split_whitespace().skip(1).map(normalize_tag));
+        }
+
+        IndexEntry {
+            keys,
+            tags,
+            meta,
+            target,
+        }
     }
 
-    pub fn do_match(&self, query: &str) -> Option<(&str, usize, MatchScore)> {
+    pub fn has_tags(&self, filters: &[String]) -> bool {
+        filters
+            .iter()
+            .all(|filter| self.tags.iter().any(|tag| tag.starts_with(filter.as_str())))
+    }
+
+    pub fn do_match(&self, query: &str, demoted: bool) -> Option<(&str, usize, MatchScore)> {
         for (key, lower) in &self.keys {
             if let Some(byte_index) = lower.find(query) {
                 let char_index = lower
@@ -80,7 +242,8 @@ impl<T> IndexEntry<T> {
                     .position(|&x| x == ' ')
                     .unwrap_or(char_index);
 
-                let score = (within_word_index, word_index, u64::MAX - self.meta.counter);
+                let counter = u64::MAX - self.meta.counter;
+                let score = (within_word_index, word_index, demoted, counter);
 
                 return Some((key, char_index, score));
             }
@@ -119,18 +282,16 @@ where
         // 1. build the index
         let mut from_index = provider.index();
 
-        // 2. load saved entries that still exist (are in the index)
+        // 2. load saved entries that still exist (are in the index),
+        //    keeping the saved meta but the freshly indexed target
         let mut from_save: Vec<_> = save
             .entries
             .into_iter()
-            .filter(|e| match from_index.iter().position(|x| *x == e.target) {
-                None => false,
-                Some(i) => {
-                    from_index.remove(i);
-                    true
-                }
+            .filter_map(|e| {
+                let i = from_index.iter().position(|x| *x == e.target)?;
+                let target = from_index.remove(i);
+                Some(IndexEntry::new(&provider, e.meta, target))
             })
-            .map(|src| IndexEntry::new(&provider, src.meta, src.target))
             .collect();
 
         // 3. create index entries that were not in the save.
@@ -163,7 +324,8 @@ where
                 })
             });
 
-            let meta = IndexEntryMeta { icon, counter: 0 };
+            let counter = provider.seed_counter(&target);
+            let meta = IndexEntryMeta { icon, counter };
 
             Some(IndexEntry::new(&provider, meta, target))
         }));
@@ -198,18 +360,22 @@ where
         });
     }
 
-    pub fn search(&self, query: &str) -> Vec<Match<usize>> {
+    pub fn search(&self, query: &str, tags: &[String]) -> Vec<Match<usize>> {
         let mut matches: Vec<_> = self
             .entries
             .iter()
             .enumerate()
+            .filter(|(_, entry)| entry.has_tags(tags))
             .filter_map(|(i, entry)| {
-                entry.do_match(query).map(|(key, index, score)| Match {
-                    key,
-                    index,
-                    score,
-                    value: i,
-                })
+                let demoted = self.provider.demoted(&entry.target);
+                entry
+                    .do_match(query, demoted)
+                    .map(|(key, index, score)| Match {
+                        key,
+                        index,
+                        score,
+                        value: i,
+                    })
             })
             .collect();
 
@@ -247,14 +413,14 @@ where
         this.provider.details(&entry.target)
     }
 
-    fn launch(&self, &target: &usize) -> Box<dyn Fn()> {
+    fn launch(&self, &target: &usize) -> Box<dyn Fn(&LaunchOptions)> {
         let this = self.borrow();
         let entry = &this.entries[target];
         let launch = this.provider.launch(&entry.target);
 
         let rc = self.clone();
-        Box::new(move || {
-            launch();
+        Box::new(move |options| {
+            launch(options);
             let mut this = rc.borrow_mut();
             this.entries[target].meta.counter += 1;
             this.save();
@@ -270,6 +436,30 @@ where
             image::open(&entry.meta.icon)?
         )
     }
+
+    fn artwork(&self, &target: &usize, kind: Artwork) -> Option<PathBuf> {
+        let this = self.borrow();
+        let entry = &this.entries[target];
+        this.provider.artwork(&entry.target, kind)
+    }
+
+    fn is_installed(&self, &target: &usize) -> bool {
+        let this = self.borrow();
+        let entry = &this.entries[target];
+        this.provider.is_installed(&entry.target)
+    }
+
+    fn tags(&self, &target: &usize) -> Vec<String> {
+        let this = self.borrow();
+        let entry = &this.entries[target];
+        this.provider.tags(&entry.target)
+    }
+
+    fn demoted(&self, &target: &usize) -> bool {
+        let this = self.borrow();
+        let entry = &this.entries[target];
+        this.provider.demoted(&entry.target)
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -324,6 +514,7 @@ macro_rules! any_search {
                     }
                 )*
 
+                self.remove_duplicates(&mut vec);
                 vec
             }
 
@@ -333,7 +524,7 @@ macro_rules! any_search {
                 }
             }
 
-            fn launch(&self, target: &AnyTarget) -> Box<dyn Fn()> {
+            fn launch(&self, target: &AnyTarget) -> Box<dyn Fn(&LaunchOptions)> {
                 match target {
                     $( AnyTarget::$variant(t) => self.$name.as_ref().unwrap().launch(t), )*
                 }
@@ -350,6 +541,36 @@ macro_rules! any_search {
                     $( AnyTarget::$variant(t) => self.$name.as_ref().unwrap().display_icon(t), )*
                 }
             }
+
+            fn seed_counter(&self, target: &AnyTarget) -> u64 {
+                match target {
+                    $( AnyTarget::$variant(t) => self.$name.as_ref().unwrap().seed_counter(t), )*
+                }
+            }
+
+            fn artwork(&self, target: &AnyTarget, kind: Artwork) -> Option<PathBuf> {
+                match target {
+                    $( AnyTarget::$variant(t) => self.$name.as_ref().unwrap().artwork(t, kind), )*
+                }
+            }
+
+            fn is_installed(&self, target: &AnyTarget) -> bool {
+                match target {
+                    $( AnyTarget::$variant(t) => self.$name.as_ref().unwrap().is_installed(t), )*
+                }
+            }
+
+            fn tags(&self, target: &AnyTarget) -> Vec<String> {
+                match target {
+                    $( AnyTarget::$variant(t) => self.$name.as_ref().unwrap().tags(t), )*
+                }
+            }
+
+            fn demoted(&self, target: &AnyTarget) -> bool {
+                match target {
+                    $( AnyTarget::$variant(t) => self.$name.as_ref().unwrap().demoted(t), )*
+                }
+            }
         }
     };
 }
@@ -358,6 +579,12 @@ any_search!(
     (Config, config, ManualTarget, SearchConfig),
     (Appx, appx, AppxTarget, AppxProvider),
     (Steam, steam, SteamTarget, SteamProvider),
+    (
+        SteamShortcut,
+        steam_shortcuts,
+        SteamShortcutTarget,
+        SteamShortcutProvider
+    ),
     (StartMenu, start_menu, StartMenuTarget, StartMenuProvider),
 );
 
@@ -366,13 +593,234 @@ impl Provider {
         let config = SearchConfig::load();
         let appx = config.appx.as_ref().map(AppxProvider::new);
         let steam = config.steam.as_ref().map(SteamProvider::new);
+        let steam_shortcuts = config.steam.as_ref().map(SteamShortcutProvider::new);
         let start_menu = config.start_menu.as_ref().map(StartMenuProvider::new);
 
         Provider {
             config: Some(config),
             appx,
             steam,
+            steam_shortcuts,
             start_menu,
         }
     }
+
+    /// Drop start menu urls like steam://rungameid/<id> for games steam lists itself
+    fn remove_duplicates(&self, targets: &mut Vec<AnyTarget>) {
+        let steam_apps: HashSet<u32> = targets
+            .iter()
+            .filter_map(|target| match target {
+                AnyTarget::Steam(target) => Some(target.app_id()),
+                _ => None,
+            })
+            .collect();
+
+        targets.retain(|target| match target {
+            AnyTarget::StartMenu(target) => match target.steam_app_id() {
+                Some(app_id) => !steam_apps.contains(&app_id),
+                None => true,
+            },
+            _ => true,
+        });
+    }
+}
+
+fn has_key<T, P: SearchProvider<T>>(provider: &P, target: &T, names: &[String]) -> bool {
+    let keys = provider.keys(target);
+    names
+        .iter()
+        .any(|name| keys.iter().any(|key| key.eq_ignore_ascii_case(name)))
+}
+
+/// Describe the manual and start menu targets with one of the given names as shortcuts, and the
+/// steam and appx ones too when `apps` is set
+fn matching_shortcuts(provider: &Provider, names: &[String], apps: bool) -> Vec<ExportShortcut> {
+    let mut shortcuts: Vec<ExportShortcut> = vec![];
+
+    if let Some(config) = &provider.config {
+        let targets = config.index().into_iter();
+        let targets = targets.filter(|t| has_key(config, t, names));
+        shortcuts.extend(targets.map(|t| t.steam_shortcut()));
+    }
+
+    if let Some(start_menu) = &provider.start_menu {
+        let targets = start_menu.index().into_iter();
+        let targets = targets.filter(|t| has_key(start_menu, t, names));
+        shortcuts.extend(targets.filter_map(|t| t.steam_shortcut()));
+    }
+
+    if !apps {
+        return shortcuts;
+    }
+
+    if let Some(steam) = &provider.steam {
+        let targets = steam.index().into_iter();
+        let targets = targets.filter(|t| has_key(steam, t, names));
+        shortcuts.extend(targets.map(|t| steam.shortcut(&t)));
+    }
+
+    if let Some(appx) = &provider.appx {
+        let targets = appx.index().into_iter();
+        let targets = targets.filter(|t| has_key(appx, t, names));
+        shortcuts.extend(targets.map(|t| t.shortcut()));
+    }
+
+    shortcuts
+}
+
+/// Add the manual and start menu targets with one of the given names to steam's shortcuts.vdf
+pub fn export_steam_shortcuts(names: &[String]) -> usize {
+    let provider = Provider::new();
+
+    let steam = match &provider.steam_shortcuts {
+        Some(steam) => steam,
+        None => {
+            crate::log!("export steam shortcuts: steam is not configured");
+            return 0;
+        }
+    };
+
+    let shortcuts = matching_shortcuts(&provider, names, false);
+
+    let added = steam.export(&shortcuts);
+    crate::log!(
+        "matched {} targets, added {} steam shortcuts, restart steam to see them",
+        shortcuts.len(),
+        added
+    );
+
+    added
+}
+
+/// A shortcut to the target, or a copy of the one it came from
+fn write_lnk(dir: &Path, shortcut: &ExportShortcut) -> std::io::Result<()> {
+    // keep the name out of the way of characters windows doesn't allow in file names
+    let name: String = shortcut
+        .name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c => c,
+        })
+        .collect();
+    let path = dir.join(format!("{}.lnk", name));
+
+    // leave what's there alone, be it from before or another target with the same name
+    if path.exists() {
+        let message = format!("{:?} already exists", path);
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            message,
+        ));
+    }
+
+    if shortcut.shortcut_path.to_lowercase().ends_with(".lnk") {
+        std::fs::copy(&shortcut.shortcut_path, path)?;
+        return Ok(());
+    }
+
+    // the target couldn't be found on the path, the link would be relative to where it's opened
+    if !Path::new(&shortcut.exe).is_absolute() {
+        let message = format!("target is not an absolute path: {:?}", shortcut.exe);
+        return Err(std::io::Error::other(message));
+    }
+
+    let mut lnk = ShellLink::new(&shortcut.exe);
+    let fields = [
+        (&mut lnk.working_dir, &shortcut.start_dir),
+        (&mut lnk.command_line_arguments, &shortcut.launch_options),
+        (&mut lnk.icon_location, &shortcut.icon),
+    ];
+    for (field, value) in fields {
+        *field = Some(value.clone()).filter(|v| v.len() > 0);
+    }
+    if shortcut.app_user_model_id.len() > 0 {
+        lnk.set_app_user_model_id(&shortcut.app_user_model_id);
+    }
+
+    let data = lnk.store().map_err(std::io::Error::other)?;
+    std::fs::write(path, data)
+}
+
+/// Write a .lnk to `dir` for each manual, start menu, steam and appx target with one of the
+/// given names
+pub fn export_shortcuts(dir: &Path, names: &[String]) -> usize {
+    let provider = Provider::new();
+    let shortcuts = matching_shortcuts(&provider, names, true);
+
+    let written = shortcuts
+        .iter()
+        .filter(|shortcut| {
+            let written = crate::attempt!(("export shortcut {:?}", shortcut.name), {
+                write_lnk(dir, shortcut)?
+            });
+            written.is_some()
+        })
+        .count();
+
+    crate::log!(
+        "matched {} targets, wrote {} shortcuts to {:?}",
+        shortcuts.len(),
+        written,
+        dir
+    );
+
+    written
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_without_arguments() {
+        assert_eq!(split_query("half life"), ("half life", vec![]));
+        assert_eq!(split_query("c--"), ("c--", vec![]));
+        assert_eq!(split_query("portal --"), ("portal", vec![]));
+    }
+
+    #[test]
+    fn query_with_arguments() {
+        assert_eq!(
+            split_query("blender -- --factory-startup"),
+            ("blender", vec!["--factory-startup".to_owned()])
+        );
+
+        assert_eq!(
+            split_query("c-- -- -windowed -w 1280"),
+            ("c--", vec!["-windowed".into(), "-w".into(), "1280".into()])
+        );
+
+        assert_eq!(
+            split_query(r#"-- +connect "my server" """#),
+            ("", vec!["+connect".into(), "my server".into(), "".into()])
+        );
+    }
+
+    #[test]
+    fn arguments_start_a_new_instance() {
+        let (_, arguments) = split_query("portal -- -novid");
+        assert!(LaunchOptions::default().focus_running());
+        assert!(!LaunchOptions {
+            arguments,
+            ..Default::default()
+        }
+        .focus_running());
+    }
+
+    #[test]
+    fn query_with_tags() {
+        assert_eq!(split_tags("portal"), ("portal", vec![]));
+        assert_eq!(
+            split_tags("portal #coop"),
+            ("portal", vec!["coop".to_owned()])
+        );
+        assert_eq!(
+            split_tags("#Co-op #backlog "),
+            ("", vec!["coop".into(), "backlog".into()])
+        );
+        // only trailing words are tags, and a lone # is text
+        assert_eq!(split_tags("c# #"), ("c# #", vec![]));
+        assert_eq!(split_tags("#1 best"), ("#1 best", vec![]));
+    }
 }
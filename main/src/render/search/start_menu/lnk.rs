@@ -1,6 +1,5 @@
 use std::{fs::File, io::Read, path::Path};
 
-use bitflags::bitflags;
 use winapi::{
     shared::minwindef::HMODULE,
     shared::minwindef::MAX_PATH,
@@ -9,7 +8,6 @@ use winapi::{
             EnumResourceNamesW, FindResourceW, FreeLibrary, LoadLibraryExW, LoadResource,
             LockResource, SizeofResource, LOAD_LIBRARY_AS_DATAFILE, LOAD_LIBRARY_AS_IMAGE_RESOURCE,
         },
-        processenv::ExpandEnvironmentStringsW,
         shellapi::{SHGetFileInfoW, SHFILEINFOW, SHGFI_DISPLAYNAME},
         shlobj::SHGetPathFromIDListW,
     },
@@ -19,73 +17,39 @@ use flat::prelude::*;
 
 use crate::common::{IconHeader, IconImageHeader};
 
-pub struct ShellLink {
-    pub link_flags: LinkFlags,
-    pub file_attributes: u32,
-    pub creation_time: u64,
-    pub access_time: u64,
-    pub write_time: u64,
-    pub file_size: u32,
-    pub icon_index: i32,
-    pub show_command: u32,
-    pub hotkey: u16,
-
-    pub link_target_id_list: Option<Vec<u8>>,
-    pub link_info: Option<LinkInfo>,
-
-    pub name: Option<String>,
-    pub relative_path: Option<String>,
-    pub working_dir: Option<String>,
-    pub command_line_arguments: Option<String>,
-    pub icon_location: Option<String>,
-
-    pub environment_variable_data: Option<String>,
-    pub icon_environment_data: Option<String>,
-
-    pub extra_data: Vec<(u32, Vec<u8>)>,
-}
-
-pub fn expand_environment_data(value: &str) -> String {
-    let utf16: Vec<u16> = value.encode_utf16().chain(std::iter::once(0)).collect();
-    let mut value = [0; 1024];
-
-    let len = unsafe {
-        ExpandEnvironmentStringsW(utf16.as_ptr(), value.as_mut_ptr(), value.len() as u32) - 1
-    };
-
-    String::from_utf16(&value[0..len as usize]).unwrap()
-}
+pub use shell_link::{Environment, Location, ShellLink};
 
 pub fn resolve(lnk: &ShellLink) -> Option<String> {
-    if let Some(env_data) = &lnk.environment_variable_data {
-        Some(expand_environment_data(env_data))
-    } else if let Some(target) = &lnk.link_target_id_list {
-        let pidlist = target.as_ptr() as _;
-        let mut value = [0; 1024];
-        let result = unsafe { SHGetPathFromIDListW(pidlist, value.as_mut_ptr()) };
-        if result == 1 {
-            let len = value.iter().position(|x| *x == 0).unwrap();
-            Some(String::from_utf16(&value[0..len]).unwrap())
-        } else {
-            None
+    match lnk.target(&Environment::current())? {
+        Location::Path(path) => Some(path),
+        Location::Uri(uri) => Some(uri),
+        // known folders move between machines, only the shell knows where they are now
+        Location::Virtual { .. } => {
+            let target = lnk.link_target_id_list.as_ref()?;
+            let pidlist = target.as_ptr() as _;
+            let mut value = [0; 1024];
+            let result = unsafe { SHGetPathFromIDListW(pidlist, value.as_mut_ptr()) };
+            if result == 1 {
+                let len = value.iter().position(|x| *x == 0).unwrap();
+                Some(String::from_utf16(&value[0..len]).unwrap())
+            } else {
+                None
+            }
         }
-    } else {
-        todo!("shell link without EnvironmentVariableDataBlock or LinkTargetIDList");
     }
 }
 
 pub fn extract_ico(lnk: &ShellLink) -> Option<Vec<u8>> {
-    let icon_path = if let Some(icon_path) = &lnk.icon_location {
-        // println!("icon A");
-        expand_environment_data(icon_path)
-    } else if let Some(env_data) = &lnk.icon_environment_data {
-        // println!("icon B");
-        expand_environment_data(env_data)
-    } else {
-        // println!("icon C");
-        resolve(lnk)?
+    let icon_path = match lnk.icon_path(&Environment::current()) {
+        Some(icon_path) => icon_path,
+        None => resolve(lnk)?,
     };
 
+    extract_ico_from(&icon_path, lnk.icon_index)
+}
+
+/// The icon in an .ico file, or the icon resource at `icon_index` of an exe or dll
+pub fn extract_ico_from(icon_path: &str, icon_index: i32) -> Option<Vec<u8>> {
     let is_ico = crate::attempt!(("check ICO magic {}", icon_path), {
         let mut magic = [0; 4];
         File::open(&icon_path)?.read_exact(&mut magic)?;
@@ -144,7 +108,7 @@ pub fn extract_ico(lnk: &ShellLink) -> Option<Vec<u8>> {
             }
         }
 
-        let mut icon_id = lnk.icon_index as isize;
+        let mut icon_id = icon_index as isize;
         icon_id *= icon_id.signum();
 
         // println!("{} {:?} {}", icon_path, libmodule, icon_id);
@@ -218,210 +182,3 @@ pub fn get_display_name<P: AsRef<Path>>(path: P) -> String {
 
     String::from_utf16(&file_info.szDisplayName[..strlen]).unwrap()
 }
-
-impl ShellLink {
-    fn load_string_data(cursor: &mut &[u8]) -> String {
-        let len = cursor.load::<u16le>().get();
-        let chars: Vec<u16> = (0..len).map(|_| cursor.load()).collect();
-        String::from_utf16(&chars).unwrap()
-    }
-
-    pub fn load(src: &[u8]) -> ShellLink {
-        let mut cursor = src;
-        let cursor = &mut cursor;
-        let header: &ShellLinkHeader = cursor.load();
-
-        let link_flags = match LinkFlags::from_bits(header.link_flags.get()) {
-            Some(flags) => flags,
-            None => panic!("invalid flags: {:x}", header.link_flags.get()),
-        };
-
-        let file_attributes = header.file_attributes.get();
-        let creation_time = header.creation_time.get();
-        let access_time = header.access_time.get();
-        let write_time = header.write_time.get();
-        let file_size = header.file_size.get();
-        let icon_index = header.icon_index.get();
-        let show_command = header.show_command.get();
-        let hotkey = header.hotkey.get();
-
-        let link_target_id_list = if link_flags.contains(LinkFlags::HAS_LINK_TARGET_ID_LIST) {
-            let size = cursor.load::<u16le>().get() as usize;
-            let data = &cursor[0..size];
-            *cursor = &cursor[size..];
-            Some(data.to_vec())
-        } else {
-            None
-        };
-
-        let link_info = if link_flags.contains(LinkFlags::HAS_LINK_INFO) {
-            let size = u32le::load(cursor).get() as usize;
-            // let data = &cursor[0..size];
-            *cursor = &cursor[size..];
-
-            None
-        // let content = &cursor[..size];
-        // *cursor = &cursor[4..];
-
-        // let header_size = cursor.load::<u32le>().get();
-        // let flags = cursor.load::<u32le>().get();
-
-        // let volume_id = if (flags & 1) == 1 {
-        //     let offset = cursor.load::<u32le>().get();
-        //     let data =
-        // } else {
-        //     *cursor = &cursor[4..];
-        //     None
-        // }
-
-        // Some(data.to_vec())
-        } else {
-            None
-        };
-
-        let name = match link_flags.contains(LinkFlags::HAS_NAME) {
-            true => Some(Self::load_string_data(cursor)),
-            false => None,
-        };
-
-        let relative_path = match link_flags.contains(LinkFlags::HAS_RELATIVE_PATH) {
-            true => Some(Self::load_string_data(cursor)),
-            false => None,
-        };
-
-        let working_dir = match link_flags.contains(LinkFlags::HAS_WORKING_DIR) {
-            true => Some(Self::load_string_data(cursor)),
-            false => None,
-        };
-
-        let command_line_arguments = match link_flags.contains(LinkFlags::HAS_ARGUMENTS) {
-            true => Some(Self::load_string_data(cursor)),
-            false => None,
-        };
-
-        let icon_location = match link_flags.contains(LinkFlags::HAS_ICON_LOCATION) {
-            true => Some(Self::load_string_data(cursor)),
-            false => None,
-        };
-
-        let mut environment_variable_data = None;
-        let mut icon_environment_data = None;
-
-        let mut extra_data = vec![];
-        loop {
-            let size = cursor.load::<u32le>().get() as usize;
-            if size < 4 {
-                break;
-            }
-
-            let sig = cursor.load::<u32le>().get();
-            match sig {
-                0xa000_0001 => {
-                    assert_eq!(size, 0x314);
-                    *cursor = &cursor[260..];
-                    let utf16: Vec<u16> = (0..260).map(|_| cursor.load()).collect();
-                    let strlen = utf16.iter().position(|x| *x == 0).unwrap();
-                    let value = String::from_utf16(&utf16[..strlen]).unwrap();
-                    environment_variable_data = Some(value);
-                }
-                0xa000_0007 => {
-                    assert_eq!(size, 0x314);
-                    *cursor = &cursor[260..];
-                    let utf16: Vec<u16> = (0..260).map(|_| cursor.load()).collect();
-                    let strlen = utf16.iter().position(|x| *x == 0).unwrap();
-                    let value = String::from_utf16(&utf16[..strlen]).unwrap();
-                    icon_environment_data = Some(value);
-                }
-                _ => {
-                    let data = &cursor[..size - 8];
-                    *cursor = &cursor[size - 8..];
-                    extra_data.push((sig, data.to_vec()))
-                }
-            }
-        }
-
-        ShellLink {
-            link_flags,
-            file_attributes,
-            creation_time,
-            access_time,
-            write_time,
-            file_size,
-            icon_index,
-            show_command,
-            hotkey,
-            link_target_id_list,
-            link_info,
-            name,
-            relative_path,
-            working_dir,
-            command_line_arguments,
-            icon_location,
-            environment_variable_data,
-            icon_environment_data,
-            extra_data,
-        }
-    }
-}
-
-flat_data!(ShellLinkHeader);
-#[repr(C, packed)]
-#[derive(Copy, Clone)]
-pub struct ShellLinkHeader {
-    pub header_size: u32le,
-    pub lnk_clsid: [u32le; 4],
-    pub link_flags: u32le,
-    pub file_attributes: u32le,
-    pub creation_time: u64le,
-    pub access_time: u64le,
-    pub write_time: u64le,
-    pub file_size: u32le,
-    pub icon_index: i32le,
-    pub show_command: u32le,
-    pub hotkey: u16le,
-    pub _reserved1: u16le,
-    pub _reserved2: u32le,
-    pub _reserved3: u32le,
-}
-
-bitflags! {
-    pub struct LinkFlags: u32 {
-        const HAS_LINK_TARGET_ID_LIST = 1 << 0;
-        const HAS_LINK_INFO = 1 << 1;
-        const HAS_NAME = 1 << 2;
-        const HAS_RELATIVE_PATH = 1 << 3;
-        const HAS_WORKING_DIR = 1 << 4;
-        const HAS_ARGUMENTS = 1 << 5;
-        const HAS_ICON_LOCATION = 1 << 6;
-        const IS_UNICODE = 1 << 7;
-        const FORCE_NO_LINK_INFO = 1 << 8;
-        const HAS_EXP_STRING = 1 << 9;
-        const RUN_IN_SEPARATE_PROCESS = 1 << 10;
-        // const UNUSED1 = 1 << 11;
-        const HAS_DARWIN_ID = 1 << 12;
-        const RUN_AS_USER = 1 << 13;
-        const HAS_EXP_ICON = 1 << 14;
-        const NO_PIDL_ALIAS = 1 << 15;
-        // const UNUSED2 = 1 << 16;
-        const RUN_WITH_SHIM_LAYER = 1 << 17;
-        const FORCE_NO_LINK_TRACK = 1 << 18;
-        const ENABLE_TARGET_METADATA = 1 << 19;
-        const DISABLE_LINK_PATH_TRACKING = 1 << 20;
-        const DISABLE_KNOWN_FOLDER_TRACKING = 1 << 21;
-        const DISABLE_KNOWN_FOLDER_ALIAS = 1 << 22;
-        const ALLOW_LINK_TO_LINK = 1 << 23;
-        const UNALIAS_ON_SAVE = 1 << 24;
-        const PREFER_ENVIRONMENT_PATH = 1 << 25;
-        const KEEP_LOCAL_ID_LIST_FOR_UNC_TARGET = 1 << 26;
-    }
-}
-
-pub struct LinkInfo {
-    pub flags: u32,
-    pub volume_id: Option<String>,
-    pub local_base_path: Option<String>,
-    pub common_network_relative_link: Option<String>,
-    pub common_path_suffix: String,
-    pub local_base_path_unicode: Option<String>,
-    pub common_path_suffix_unicode: Option<String>,
-}
@@ -1,122 +1,282 @@
-use std::{fs::File, io::prelude::*, io::Cursor, io::Error, io::ErrorKind, path::PathBuf};
+use std::{
+    cell::Cell, fs::File, io::prelude::*, io::Cursor, io::Error, io::ErrorKind, path::Path,
+    path::PathBuf, process::Command, rc::Rc,
+};
 
 use image::{ico::IcoDecoder, DynamicImage};
-use winapi::um::shellapi::ShellExecuteW;
 
 mod lnk;
-use lnk::ShellLink;
+use lnk::{Environment, ShellLink};
 
-use crate::common::{RecursiveSearch, ToWide};
+use start_menu_index::{is_url, url, EntryFilter, InternetShortcut, Loader};
 
-use super::SearchProvider;
+use crate::common::{focus_running, join_arguments, shell_execute, RunningMatch};
+
+use super::{steam::ExportShortcut, LaunchOptions, SearchProvider};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct StartMenuConfig {}
+pub struct StartMenuConfig {
+    // start lnk targets with the arguments and working directory of the lnk instead of
+    // opening it through the shell, so the process can be tracked. the lnk's window state
+    // only applies through the shell
+    #[serde(default)]
+    launch_directly: bool,
+    // folders to search along with the start menus, like the desktop or a portable apps
+    // folder. %VAR% is expanded
+    #[serde(default)]
+    roots: Vec<String>,
+    // extensions of the files lnks point to, empty allows every extension
+    #[serde(default)]
+    target_extensions: Vec<String>,
+    #[serde(default = "default_exclude_target_extensions")]
+    exclude_target_extensions: Vec<String>,
+    // patterns for relative paths or display names to leave out, like *Uninstall* or Games/*.
+    // `*` matches anything and `?` any one character, ignoring case
+    #[serde(default)]
+    exclude: Vec<String>,
+}
+
+// documents and help files installed along with programs
+fn default_exclude_target_extensions() -> Vec<String> {
+    let extensions = ["chm", "txt", "rtf", "pdf", "html", "ini"];
+    extensions.iter().map(|&ext| ext.into()).collect()
+}
+
+// the start menus of every user and of the current one
+const START_MENUS: [&str; 2] = [
+    r"%ProgramData%\Microsoft\Windows\Start Menu\Programs",
+    r"%APPDATA%\Microsoft\Windows\Start Menu\Programs",
+];
 
-pub struct StartMenuProvider {}
+pub struct StartMenuProvider {
+    launch_directly: bool,
+    roots: Vec<PathBuf>,
+    filter: EntryFilter,
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Hash)]
 pub struct StartMenuTarget {
     name: String,
     relative: String,
     lnk_path: PathBuf,
+    // for .url files and links to them, launched through the shell
+    #[serde(default)]
+    url: Option<String>,
 }
 
 impl StartMenuProvider {
-    pub fn new(_config: &StartMenuConfig) -> StartMenuProvider {
-        StartMenuProvider {}
+    pub fn new(config: &StartMenuConfig) -> StartMenuProvider {
+        let env = Environment::current();
+        let roots = START_MENUS.iter().map(|&root| root.to_owned());
+        let roots = roots.chain(config.roots.iter().cloned());
+
+        StartMenuProvider {
+            launch_directly: config.launch_directly,
+            roots: roots.map(|root| PathBuf::from(env.expand(&root))).collect(),
+            filter: EntryFilter {
+                include: config.target_extensions.clone(),
+                exclude: config.exclude_target_extensions.clone(),
+                patterns: config.exclude.clone(),
+            },
+        }
     }
 }
 
-impl SearchProvider<StartMenuTarget> for StartMenuProvider {
-    fn index(&self) -> Vec<StartMenuTarget> {
-        let appdata = std::env::var("APPDATA").unwrap();
-        let roots = [
-            PathBuf::from(r"C:\ProgramData\Microsoft\Windows\Start Menu\Programs"),
-            PathBuf::from(appdata).join(r"Microsoft\Windows\Start Menu\Programs"),
-        ];
-
-        let mut vec: Vec<_> = roots
-            .iter()
-            .map(|root| {
-                let iter = RecursiveSearch::new(&root).into_iter();
-                iter.map(move |entry| {
-                    let relative = entry.path().strip_prefix(&root).unwrap().to_owned();
-                    (entry, relative)
-                })
-            })
-            .flatten()
-            // select only .lnk files
-            .filter_map(|(entry, relative)| {
-                let path = entry.path();
-                let ext = path.extension().and_then(|x| x.to_str())?;
-                match ext {
-                    "lnk" => Some((path, relative)),
-                    "ini" | "url" => None,
-                    _ => {
-                        println!("unknown start menu entry: {:?}", relative);
-                        None
-                    }
-                }
-            })
-            // open and parse the .lnk files
-            .filter_map(|(path, relative)| {
-                let target = crate::attempt!(("resolve lnk {:?}", path), {
-                    let mut raw = vec![];
-                    File::open(&path)?.read_to_end(&mut raw)?;
-                    let lnk = ShellLink::load(&raw);
-
-                    let target = lnk::resolve(&lnk)?;
-                    PathBuf::from(target)
-                });
-
-                Some((path, relative, target))
-            })
-            // select only .lnk files that point to 'exe', 'msc', 'url' files
-            .filter(|(path, _, target)| match target {
-                None => true, // allow links that couldn't be resolved
-                Some(target) => match target.extension().and_then(|x| x.to_str()) {
-                    None => false,
-                    Some(ext) => match ext {
-                        "exe" | "msc" => true,
-                        "url" | "chm" | "txt" | "rtf" | "pdf" | "html" | "ini" => false,
-                        ext => {
-                            println!("Unknown lnk target extension: {} {:?}", ext, path);
-                            true
-                        }
-                    },
-                },
-            })
-            // get display names and add to the tuple
-            .map(|(path, relative, target)| {
-                let name = lnk::get_display_name(&path);
-                (path, relative, target, name)
-            })
-            .collect();
+fn load_lnk(path: &Path) -> Option<ShellLink> {
+    crate::attempt!(("load lnk {:?}", path), {
+        let mut raw = vec![];
+        File::open(&path)?.read_to_end(&mut raw)?;
+        ShellLink::load(&raw)?
+    })
+}
 
-        for i in (0..vec.len()).rev() {
-            // deduplicate .lnk files that either:
-            //   - are in the same relative path within the start menu
-            //   - have the same target and name
+fn load_url(path: &Path) -> Option<InternetShortcut> {
+    crate::attempt!(("load url {:?}", path), InternetShortcut::load(path)?)
+}
 
-            let a = &vec[i];
-            let other = vec[..i]
-                .iter()
-                .find(|b| a.1 == b.1 || (a.2 == b.2 && a.3 == b.3))
-                .is_some();
+fn resolve_lnk(path: &Path) -> Option<PathBuf> {
+    let lnk = load_lnk(path)?;
+    resolve_loaded(path, &lnk)
+}
 
-            if other {
-                vec.remove(i);
-            }
+fn resolve_loaded(path: &Path, lnk: &ShellLink) -> Option<PathBuf> {
+    crate::attempt!(("resolve lnk {:?}", path), {
+        let target = lnk::resolve(lnk)?;
+        PathBuf::from(target)
+    })
+}
+
+// reads the entries of the index through the shell, logging the ones that fail
+struct Shell;
+
+impl Loader for Shell {
+    fn load_lnk(&self, path: &Path) -> Option<ShellLink> {
+        load_lnk(path)
+    }
+
+    fn load_url(&self, path: &Path) -> Option<InternetShortcut> {
+        load_url(path)
+    }
+
+    fn resolve(&self, path: &Path, lnk: &ShellLink) -> Option<PathBuf> {
+        resolve_loaded(path, lnk)
+    }
+
+    fn display_name(&self, path: &Path) -> String {
+        lnk::get_display_name(path)
+    }
+}
+
+/// Launch the target of a lnk directly, so extra arguments can be appended to its own
+fn launch_with_arguments(path: &Path, arguments: &[String]) -> Option<u32> {
+    let lnk = load_lnk(path)?;
+    let target = lnk::resolve(&lnk)?;
+    let env = Environment::current();
+
+    // expanded like the shell does when it opens the lnk itself
+    let parameters: Vec<_> = lnk
+        .command_line_arguments
+        .iter()
+        .map(|lnk_arguments| env.expand(lnk_arguments))
+        .chain(std::iter::once(join_arguments(arguments)))
+        .collect();
+
+    let working_dir = lnk.working_dir.as_ref().map(|d| env.expand(d));
+    let working_dir = working_dir.as_ref().map(Path::new);
+
+    let parameters = parameters.join(" ");
+    shell_execute(
+        target.as_str(),
+        Some(&parameters),
+        working_dir,
+        lnk.show_command as i32,
+    )
+}
+
+/// Start the target of a lnk as a child process, with the lnk's arguments before `arguments`
+fn spawn_lnk(path: &Path, arguments: &[String]) -> Option<u32> {
+    use std::os::windows::process::CommandExt;
+
+    let lnk = load_lnk(path)?;
+    let target = lnk::resolve(&lnk)?;
+    let env = Environment::current();
+
+    crate::attempt!(("launch lnk target {:?}", path), {
+        let mut command = Command::new(&target);
+        // the lnk holds a command line already, quoted the way its target expects
+        if let Some(lnk_arguments) = &lnk.command_line_arguments {
+            command.raw_arg(env.expand(lnk_arguments));
         }
+        command.args(arguments);
+
+        // the shell starts targets in their own directory when the lnk doesn't name one
+        let working_dir = match &lnk.working_dir {
+            Some(dir) => Some(PathBuf::from(env.expand(dir))),
+            None => Path::new(&target).parent().map(Path::to_owned),
+        };
+        if let Some(dir) = working_dir {
+            command.current_dir(dir);
+        }
+
+        command.spawn()?.id()
+    })
+}
 
-        // declare new variable for deduplication
-        vec.into_iter()
-            // construct index entries
-            .map(|(lnk_path, relative, _, name)| StartMenuTarget {
-                name,
-                lnk_path,
-                relative: relative.to_str().unwrap().to_owned(),
+/// What a lnk runs, the way the details show it: the command line, the directory it
+/// starts in, and the hotkey and window state if they are set
+fn describe_lnk(lnk: &ShellLink) -> Option<String> {
+    let env = Environment::current();
+    let target = lnk::resolve(lnk)?;
+
+    let mut details = match target.contains(' ') {
+        true => format!("\"{}\"", target),
+        false => target,
+    };
+
+    if let Some(arguments) = &lnk.command_line_arguments {
+        details += &format!(" {}", env.expand(arguments));
+    }
+
+    if let Some(dir) = &lnk.working_dir {
+        details += &format!(", in {}", env.expand(dir));
+    }
+
+    if let Some(hotkey) = lnk.hotkey_name() {
+        details += &format!(", {}", hotkey);
+    }
+
+    match lnk.show_command {
+        shell_link::SW_SHOWMAXIMIZED => details += ", maximized",
+        shell_link::SW_SHOWMINNOACTIVE => details += ", minimized",
+        _ => {}
+    }
+
+    Some(details)
+}
+
+impl StartMenuTarget {
+    /// The game a steam:// url starts, so it isn't listed twice along with steam's own target
+    pub fn steam_app_id(&self) -> Option<u32> {
+        url::steam_app_id(self.url.as_ref()?)
+    }
+
+    /// The .url file, which is either the entry itself or what its lnk points to
+    fn internet_shortcut(&self) -> Option<InternetShortcut> {
+        match is_url(&self.lnk_path) {
+            true => load_url(&self.lnk_path),
+            false => load_url(&resolve_lnk(&self.lnk_path)?),
+        }
+    }
+
+    /// Describe the target as a steam shortcut, steam launches the lnk target directly
+    pub fn steam_shortcut(&self) -> Option<ExportShortcut> {
+        // explorer opens urls with whatever handles them
+        if let Some(url) = &self.url {
+            let windir = std::env::var("windir").unwrap_or_else(|_| r"C:\Windows".into());
+
+            return Some(ExportShortcut {
+                name: self.name.clone(),
+                exe: Path::new(&windir)
+                    .join("explorer.exe")
+                    .to_string_lossy()
+                    .into_owned(),
+                launch_options: url.clone(),
+                ..Default::default()
+            });
+        }
+
+        let lnk = load_lnk(&self.lnk_path)?;
+        let exe = lnk::resolve(&lnk)?;
+
+        let start_dir = match &lnk.working_dir {
+            Some(dir) => Environment::current().expand(dir),
+            None => Path::new(&exe)
+                .parent()
+                .map(|p| p.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        };
+
+        Some(ExportShortcut {
+            name: self.name.clone(),
+            icon: exe.clone(),
+            exe,
+            start_dir,
+            launch_options: lnk.command_line_arguments.clone().unwrap_or_default(),
+            shortcut_path: self.lnk_path.to_string_lossy().into_owned(),
+            app_user_model_id: lnk.app_user_model_id().unwrap_or_default().to_owned(),
+        })
+    }
+}
+
+impl SearchProvider<StartMenuTarget> for StartMenuProvider {
+    fn index(&self) -> Vec<StartMenuTarget> {
+        start_menu_index::index(&self.roots, &self.filter, &Shell)
+            .into_iter()
+            .map(|entry| StartMenuTarget {
+                name: entry.name,
+                relative: entry.relative.to_str().unwrap().to_owned(),
+                lnk_path: entry.path,
+                url: entry.url,
             })
             .collect()
     }
@@ -139,35 +299,71 @@ impl SearchProvider<StartMenuTarget> for StartMenuProvider {
         ]
     }
 
-    fn launch(&self, entry: &StartMenuTarget) -> Box<dyn Fn()> {
+    fn launch(&self, entry: &StartMenuTarget) -> Box<dyn Fn(&LaunchOptions)> {
+        if let Some(url) = entry.url.clone() {
+            // the handler of the url is usually running already, there is nothing to focus,
+            // and urls don't take arguments
+            return Box::new(move |_| {
+                shell_execute(url.as_str(), None, None, 1);
+            });
+        }
+
         let lnk_path = entry.lnk_path.clone();
+        let launch_directly = self.launch_directly;
+        let pid = Rc::new(Cell::new(None));
 
-        Box::new(move || {
-            let op = "open".to_wide();
-            let raw = lnk_path.to_wide();
-
-            unsafe {
-                ShellExecuteW(
-                    std::ptr::null_mut(),
-                    op.as_ptr(),
-                    raw.as_ptr(),
-                    std::ptr::null(),
-                    std::ptr::null(),
-                    1,
-                );
+        Box::new(move |options| {
+            if options.focus_running() {
+                let running = RunningMatch {
+                    pid: pid.get(),
+                    exe: resolve_lnk(&lnk_path),
+                    ..Default::default()
+                };
+
+                if focus_running(&running) {
+                    return;
+                }
+            }
+
+            let launched = match options.arguments.len() {
+                _ if launch_directly => spawn_lnk(&lnk_path, &options.arguments),
+                // the shell applies the lnk's window state and the rest of its settings
+                0 => shell_execute(lnk_path.as_path(), None, None, 1),
+                _ => launch_with_arguments(&lnk_path, &options.arguments),
+            };
+            if launched.is_some() {
+                pid.set(launched);
             }
         })
     }
 
     fn details(&self, entry: &StartMenuTarget) -> String {
-        entry.relative.clone()
+        if let Some(url) = &entry.url {
+            return format!("{}, {}", entry.relative, url);
+        }
+
+        match load_lnk(&entry.lnk_path).as_ref().and_then(describe_lnk) {
+            Some(command) => format!("{}: {}", entry.relative, command),
+            None => entry.relative.clone(),
+        }
     }
 
     fn display_icon(&self, entry: &StartMenuTarget) -> Option<image::DynamicImage> {
+        if entry.url.is_some() {
+            let shortcut = entry.internet_shortcut()?;
+            let icon_file = Environment::current().expand(shortcut.icon_file.as_ref()?);
+            let data = lnk::extract_ico_from(&icon_file, shortcut.icon_index)?;
+
+            return crate::attempt!(("get url icon {:?}", entry.lnk_path), {
+                let decoder = IcoDecoder::new_unchecked(Cursor::new(&data))?;
+                DynamicImage::from_decoder(decoder)?
+            });
+        }
+
         crate::attempt!(("get lnk icon {:?}", entry.lnk_path), {
             let mut raw = vec![];
             File::open(&entry.lnk_path)?.read_to_end(&mut raw)?;
-            let lnk = ShellLink::load(&raw);
+            let lnk = ShellLink::load(&raw)?;
 
             let data = match lnk::extract_ico(&lnk) {
                 Some(data) => data,
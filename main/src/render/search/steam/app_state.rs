@@ -0,0 +1,191 @@
+// steamapps/appmanifest_<id>.acf
+#[derive(Deserialize, Debug)]
+pub struct AppManifest {
+    #[serde(rename = "AppState", alias = "appstate")]
+    pub app_state: AppState,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AppState {
+    #[serde(rename = "appid", alias = "appID")]
+    pub app_id: u32,
+    pub name: String,
+    #[serde(rename = "installdir")]
+    pub install_dir: String,
+    #[serde(rename = "StateFlags", default)]
+    pub state_flags: u32,
+    #[serde(rename = "SizeOnDisk", default)]
+    pub size_on_disk: u64,
+    #[serde(rename = "LastUpdated", default)]
+    pub last_updated: u64,
+    #[serde(rename = "BytesToDownload", default)]
+    pub bytes_to_download: u64,
+    #[serde(rename = "BytesDownloaded", default)]
+    pub bytes_downloaded: u64,
+    #[serde(rename = "UpdateResult", default)]
+    pub update_result: u32,
+    // steam id of the account that installed or last updated the app
+    #[serde(rename = "LastOwner", default)]
+    pub last_owner: u64,
+}
+
+// EAppState from the steamworks sdk
+const UPDATE_REQUIRED: u32 = 0x2;
+const FULLY_INSTALLED: u32 = 0x4;
+const FILES_MISSING: u32 = 0x20;
+const FILES_CORRUPT: u32 = 0x80;
+const UPDATE_RUNNING: u32 = 0x100;
+const UPDATE_PAUSED: u32 = 0x200;
+const UPDATE_STARTED: u32 = 0x400;
+const UNINSTALLING: u32 = 0x800;
+const RECONFIGURING: u32 = 0x10000;
+const VALIDATING: u32 = 0x20000;
+const ADDING_FILES: u32 = 0x40000;
+const PREALLOCATING: u32 = 0x80000;
+const DOWNLOADING: u32 = 0x100000;
+const STAGING: u32 = 0x200000;
+const COMMITTING: u32 = 0x400000;
+
+// any of these keep steam from starting the game
+const BLOCKING: u32 = UPDATE_REQUIRED
+    | FILES_MISSING
+    | FILES_CORRUPT
+    | UPDATE_RUNNING
+    | UPDATE_PAUSED
+    | UPDATE_STARTED
+    | UNINSTALLING
+    | RECONFIGURING
+    | VALIDATING
+    | ADDING_FILES
+    | PREALLOCATING
+    | DOWNLOADING
+    | STAGING
+    | COMMITTING;
+
+const UPDATING: u32 =
+    UPDATE_RUNNING | UPDATE_STARTED | DOWNLOADING | PREALLOCATING | STAGING | COMMITTING;
+
+/// The install and update state of an app, as of the last index
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct InstallState {
+    pub flags: u32,
+    pub size_on_disk: u64,
+    // unix time
+    pub last_updated: u64,
+    pub bytes_to_download: u64,
+    pub bytes_downloaded: u64,
+    pub update_result: u32,
+}
+
+impl InstallState {
+    pub fn from_manifest(state: &AppState) -> InstallState {
+        InstallState {
+            flags: state.state_flags,
+            size_on_disk: state.size_on_disk,
+            last_updated: state.last_updated,
+            bytes_to_download: state.bytes_to_download,
+            bytes_downloaded: state.bytes_downloaded,
+            update_result: state.update_result,
+        }
+    }
+
+    fn has(&self, flags: u32) -> bool {
+        self.flags & flags != 0
+    }
+
+    /// Whether steam would start the app now rather than update or repair it first.
+    /// Entries saved before the flags were read have none, and are assumed to be fine
+    pub fn can_launch(&self) -> bool {
+        self.flags == 0 || (self.has(FULLY_INSTALLED) && !self.has(BLOCKING))
+    }
+
+    fn progress(&self) -> Option<u64> {
+        match self.bytes_to_download {
+            0 => None,
+            total => Some(self.bytes_downloaded.min(total) * 100 / total),
+        }
+    }
+
+    pub fn describe(&self) -> Option<String> {
+        let with_progress = |text: &str| match self.progress() {
+            Some(percent) => format!("{} {}%", text, percent),
+            None => text.to_owned(),
+        };
+
+        let text = if self.flags == 0 {
+            return None;
+        } else if self.has(UNINSTALLING) {
+            "uninstalling".to_owned()
+        } else if self.has(VALIDATING) {
+            "validating".to_owned()
+        } else if self.has(UPDATE_PAUSED) {
+            with_progress("update paused")
+        } else if self.has(UPDATING) {
+            with_progress("downloading")
+        } else if self.has(UPDATE_REQUIRED) && self.update_result != 0 {
+            format!("update failed ({})", self.update_result)
+        } else if self.has(UPDATE_REQUIRED) {
+            "update required".to_owned()
+        } else if self.has(FILES_MISSING | FILES_CORRUPT) {
+            "needs repair".to_owned()
+        } else if self.has(FULLY_INSTALLED) {
+            format!("fully installed, {}", describe_size(self.size_on_disk))
+        } else {
+            return None;
+        };
+
+        Some(text)
+    }
+}
+
+pub fn describe_size(bytes: u64) -> String {
+    const GB: f64 = (1u64 << 30) as f64;
+    const MB: f64 = (1u64 << 20) as f64;
+
+    let bytes = bytes as f64;
+    if bytes >= 10.0 * GB {
+        format!("{:.0} GB", bytes / GB)
+    } else if bytes >= GB {
+        format!("{:.1} GB", bytes / GB)
+    } else {
+        format!("{:.0} MB", bytes / MB)
+    }
+}
+
+#[test]
+fn app_manifests() -> Result<(), Box<dyn std::error::Error>> {
+    use std::path::Path;
+
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/steam");
+    let read = |name: &str| -> Result<InstallState, Box<dyn std::error::Error>> {
+        let content = std::fs::read_to_string(fixtures.join(name))?;
+        let manifest: AppManifest = super::vdf_text::from_str(&content)?;
+        Ok(InstallState::from_manifest(&manifest.app_state))
+    };
+
+    let installed = read("appmanifest_620.acf")?;
+    assert!(installed.can_launch());
+    assert_eq!(installed.last_updated, 1_601_400_000);
+    assert_eq!(
+        installed.describe().as_deref(),
+        Some("fully installed, 12 GB")
+    );
+
+    let updating = read("appmanifest_1145360.acf")?;
+    assert!(!updating.can_launch());
+    assert_eq!(updating.describe().as_deref(), Some("downloading 43%"));
+
+    let required = InstallState {
+        flags: FULLY_INSTALLED | UPDATE_REQUIRED,
+        ..updating.clone()
+    };
+    assert!(!required.can_launch());
+    assert_eq!(required.describe().as_deref(), Some("update required"));
+
+    assert!(InstallState::default().can_launch());
+    assert_eq!(InstallState::default().describe(), None);
+    assert_eq!(describe_size(54 << 30), "54 GB");
+    assert_eq!(describe_size(3 << 29), "1.5 GB");
+
+    Ok(())
+}
@@ -0,0 +1,333 @@
+use serde::de::DeserializeOwned;
+
+use flat::prelude::*;
+
+use super::vdf_binary::{ValveDeserializer, ValveError, ValveReader};
+
+flat_data!(AppInfoHeader);
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+struct AppInfoHeader {
+    magic: u32le,
+    universe: u32le,
+}
+
+flat_data!(AppInfoEntryHeader);
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+struct AppInfoEntryHeader {
+    app_id: u32le,
+    size: u32le,
+    state: u32le,
+    last_updated: u32le,
+    pics_token: u64le,
+    sha1: [u8; 20],
+    change_number: u32le,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AppInfoVersion {
+    // the original layout
+    V27,
+    // adds a hash of the binary key values to each entry
+    V28,
+    // moves keys into a string table at the end of the file
+    V29,
+}
+
+impl AppInfoVersion {
+    pub fn from_magic(magic: u32) -> Result<AppInfoVersion, ValveError> {
+        match magic {
+            0x0756_4427 => Ok(AppInfoVersion::V27),
+            0x0756_4428 => Ok(AppInfoVersion::V28),
+            0x0756_4429 => Ok(AppInfoVersion::V29),
+            magic => Err(ValveError(format!(
+                "unsupported appinfo.vdf version (magic {:#010x})",
+                magic
+            ))),
+        }
+    }
+
+    fn has_binary_hash(self) -> bool {
+        self != AppInfoVersion::V27
+    }
+}
+
+pub struct AppInfoFile<'a> {
+    pub version: AppInfoVersion,
+    pub universe: u32,
+    entries: &'a [u8],
+    strings: Vec<&'a str>,
+}
+
+pub struct AppInfoRecord<'s, 'a> {
+    pub app_id: u32,
+    pub last_updated: u32,
+    pub change_number: u32,
+    data: &'a [u8],
+    strings: Option<&'s [&'a str]>,
+}
+
+fn truncated(what: &str) -> ValveError {
+    ValveError(format!("appinfo.vdf is truncated ({})", what))
+}
+
+fn read_string_table(mut src: &[u8]) -> Result<Vec<&str>, ValveError> {
+    if src.len() < 4 {
+        return Err(truncated("string table"));
+    }
+
+    let count = src.load::<u32le>().get();
+    let mut strings = vec![];
+    for _ in 0..count {
+        let strlen = match src.iter().position(|&c| c == 0) {
+            Some(len) => len,
+            None => return Err(truncated("string table")),
+        };
+
+        let value = std::str::from_utf8(&src[..strlen])
+            .map_err(|e| ValveError(format!("invalid string table entry: {}", e)))?;
+
+        strings.push(value);
+        src = &src[strlen + 1..];
+    }
+
+    Ok(strings)
+}
+
+impl<'a> AppInfoFile<'a> {
+    pub fn parse(mut content: &'a [u8]) -> Result<AppInfoFile<'a>, ValveError> {
+        let file = content;
+        if content.len() < AppInfoHeader::SIZE {
+            return Err(truncated("header"));
+        }
+
+        let header: &AppInfoHeader = content.load();
+        let version = AppInfoVersion::from_magic(header.magic.get())?;
+        let universe = header.universe.get();
+
+        let (entries, strings) = match version {
+            AppInfoVersion::V27 | AppInfoVersion::V28 => (content, vec![]),
+            AppInfoVersion::V29 => {
+                if content.len() < 8 {
+                    return Err(truncated("header"));
+                }
+
+                let offset = content.load::<u64le>().get() as usize;
+                if offset < AppInfoHeader::SIZE + 8 || offset > file.len() {
+                    return Err(truncated("string table offset"));
+                }
+
+                let entries = &file[AppInfoHeader::SIZE + 8..offset];
+                let strings = read_string_table(&file[offset..])?;
+                (entries, strings)
+            }
+        };
+
+        Ok(AppInfoFile {
+            version,
+            universe,
+            entries,
+            strings,
+        })
+    }
+
+    pub fn records(&self) -> Result<Vec<AppInfoRecord<'_, 'a>>, ValveError> {
+        let mut content = self.entries;
+        let mut out = vec![];
+
+        let strings = match self.version {
+            AppInfoVersion::V29 => Some(self.strings.as_slice()),
+            _ => None,
+        };
+
+        // the list of entries ends with an app id of 0
+        while content.len() >= 4 && content[..4] != [0; 4] {
+            if content.len() < AppInfoEntryHeader::SIZE {
+                return Err(truncated("entry header"));
+            }
+
+            let entry: &AppInfoEntryHeader = content.load();
+
+            // size counts everything after the size field itself
+            let header_size = AppInfoEntryHeader::SIZE - 8;
+            let mut size = entry.size.get() as usize;
+            if size < header_size || size - header_size > content.len() {
+                return Err(truncated("entry"));
+            }
+            size -= header_size;
+
+            if self.version.has_binary_hash() {
+                if size < 20 {
+                    return Err(truncated("entry"));
+                }
+
+                let _binary_sha1: &[u8; 20] = content.load();
+                size -= 20;
+            }
+
+            let data = content.load_slice(size);
+
+            out.push(AppInfoRecord {
+                app_id: entry.app_id.get(),
+                last_updated: entry.last_updated.get(),
+                change_number: entry.change_number.get(),
+                data,
+                strings,
+            });
+        }
+
+        Ok(out)
+    }
+}
+
+impl<'s, 'a> AppInfoRecord<'s, 'a> {
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, ValveError> {
+        let mut data = self.data;
+        match self.strings {
+            None => T::deserialize(&mut ValveDeserializer::new(&mut data)),
+            Some(strings) => T::deserialize(&mut ValveDeserializer::with_string_table(
+                &mut data, strings,
+            )),
+        }
+    }
+
+    pub fn visit<F>(&self, mut f: F)
+    where
+        F: FnMut(&mut ValveReader),
+    {
+        let mut data = self.data;
+        match self.strings {
+            None => f(&mut ValveReader::new(&mut data)),
+            Some(strings) => f(&mut ValveReader::with_string_table(&mut data, strings)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // { "appinfo" { "appid" 440 "common" { "name" "Team Fortress 2" } } }
+    fn key_values(keys: Option<[u32; 4]>) -> Vec<u8> {
+        let mut out = vec![];
+        let key = |out: &mut Vec<u8>, name: &str, index: usize| match keys {
+            None => out.extend(name.bytes().chain(Some(0))),
+            Some(keys) => out.extend(&keys[index].to_le_bytes()),
+        };
+
+        out.push(0x00);
+        key(&mut out, "appinfo", 0);
+        out.push(0x02);
+        key(&mut out, "appid", 1);
+        out.extend(&440i32.to_le_bytes());
+        out.push(0x00);
+        key(&mut out, "common", 2);
+        out.push(0x01);
+        key(&mut out, "name", 3);
+        out.extend(b"Team Fortress 2\0");
+        out.extend(&[0x08, 0x08, 0x08]);
+        out
+    }
+
+    fn entry(version: AppInfoVersion, data: &[u8]) -> Vec<u8> {
+        let mut out = vec![];
+        let hash = if version.has_binary_hash() { 20 } else { 0 };
+
+        out.extend(&440u32.to_le_bytes());
+        out.extend(&((AppInfoEntryHeader::SIZE - 8 + hash + data.len()) as u32).to_le_bytes());
+        out.extend(&2u32.to_le_bytes());
+        out.extend(&1_600_000_000u32.to_le_bytes());
+        out.extend(&0u64.to_le_bytes());
+        out.extend(&[0; 20]);
+        out.extend(&1234u32.to_le_bytes());
+        out.extend(std::iter::repeat(0).take(hash));
+        out.extend(data);
+        out
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Entry {
+        appinfo: Info,
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Info {
+        appid: i32,
+        common: Common,
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Common {
+        name: String,
+    }
+
+    fn check(file: &[u8], version: AppInfoVersion) {
+        let file = AppInfoFile::parse(file).unwrap();
+        assert_eq!(file.version, version);
+        assert_eq!(file.universe, 1);
+
+        let records = file.records().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].app_id, 440);
+        assert_eq!(records[0].change_number, 1234);
+
+        let entry: Entry = records[0].deserialize().unwrap();
+        assert_eq!(entry.appinfo.appid, 440);
+        assert_eq!(entry.appinfo.common.name, "Team Fortress 2");
+    }
+
+    #[test]
+    fn versions_27_and_28() {
+        for &(magic, version) in &[
+            (0x0756_4427u32, AppInfoVersion::V27),
+            (0x0756_4428u32, AppInfoVersion::V28),
+        ] {
+            let mut file = vec![];
+            file.extend(&magic.to_le_bytes());
+            file.extend(&1u32.to_le_bytes());
+            file.extend(entry(version, &key_values(None)));
+            file.extend(&[0; 4]);
+
+            check(&file, version);
+        }
+    }
+
+    #[test]
+    fn version_29() {
+        let mut entries = entry(AppInfoVersion::V29, &key_values(Some([3, 0, 2, 1])));
+        entries.extend(&[0; 4]);
+
+        let mut file = vec![];
+        file.extend(&0x0756_4429u32.to_le_bytes());
+        file.extend(&1u32.to_le_bytes());
+        file.extend(&(16 + entries.len() as u64).to_le_bytes());
+        file.extend(entries);
+        file.extend(&4u32.to_le_bytes());
+        file.extend(b"appid\0name\0common\0appinfo\0");
+
+        check(&file, AppInfoVersion::V29);
+    }
+
+    #[test]
+    fn unknown_version() {
+        let mut file = vec![];
+        file.extend(&0x0756_4426u32.to_le_bytes());
+        file.extend(&1u32.to_le_bytes());
+
+        let error = AppInfoFile::parse(&file).err().unwrap();
+        assert!(error.0.contains("0x07564426"), "{}", error);
+    }
+
+    #[test]
+    fn truncated_entry() {
+        let mut file = vec![];
+        file.extend(&0x0756_4428u32.to_le_bytes());
+        file.extend(&1u32.to_le_bytes());
+        let entry = entry(AppInfoVersion::V28, &key_values(None));
+        file.extend(&entry[..entry.len() - 10]);
+
+        let file = AppInfoFile::parse(&file).unwrap();
+        assert!(file.records().is_err());
+    }
+}
@@ -0,0 +1,148 @@
+use std::{collections::HashMap, path::Path};
+
+use super::{
+    user::{shared_config_apps, user_dir, SteamUser},
+    vdf_text::{self, AnyValue, TextError},
+};
+
+// an entry of userdata/<account id>/config/cloudstorage/cloud-storage-namespace-1.json,
+// which is a list of [key, entry] pairs
+#[derive(Deserialize, Debug)]
+struct CloudEntry {
+    key: String,
+    #[serde(default)]
+    is_deleted: bool,
+    // json of the stored value
+    value: Option<String>,
+}
+
+// the value of a user-collections.<id> entry
+#[derive(Deserialize, Debug)]
+struct UserCollection {
+    name: String,
+    #[serde(default)]
+    added: Vec<u32>,
+    #[serde(default)]
+    removed: Vec<u32>,
+    // dynamic collections are a filter instead of a list, and are skipped
+    #[serde(rename = "filterSpec")]
+    filter_spec: Option<serde_json::Value>,
+}
+
+fn add(out: &mut HashMap<u32, Vec<String>>, app_id: u32, name: &str) {
+    let names = out.entry(app_id).or_default();
+    if !names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+        names.push(name.to_owned());
+    }
+}
+
+/// Add the collections of another account
+pub fn merge_collections(into: &mut HashMap<u32, Vec<String>>, other: HashMap<u32, Vec<String>>) {
+    for (app_id, names) in other {
+        for name in names {
+            add(into, app_id, &name);
+        }
+    }
+}
+
+/// Parse the collections of the library, from the cloud storage of newer clients
+pub fn parse_cloud_collections(
+    content: &str,
+) -> Result<HashMap<u32, Vec<String>>, serde_json::Error> {
+    let entries: Vec<(String, CloudEntry)> = serde_json::from_str(content)?;
+
+    let mut out = HashMap::new();
+    for (_, entry) in entries {
+        if entry.is_deleted || !entry.key.starts_with("user-collections.") {
+            continue;
+        }
+
+        let value = match &entry.value {
+            Some(value) => value,
+            None => continue,
+        };
+
+        let collection = crate::attempt!(("read steam collection {}", entry.key), {
+            serde_json::from_str::<UserCollection>(value)?
+        });
+
+        let collection = match collection {
+            Some(c) if c.filter_spec.is_none() => c,
+            _ => continue,
+        };
+
+        for &app_id in &collection.added {
+            if !collection.removed.contains(&app_id) {
+                add(&mut out, app_id, &collection.name);
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// Parse the categories older clients keep as `tags` in sharedconfig.vdf
+pub fn parse_shared_tags(content: &str) -> Result<HashMap<u32, Vec<String>>, TextError> {
+    let root = vdf_text::parse(content)?;
+    let apps = shared_config_apps(&root).map(AnyValue::entries);
+
+    let mut out = HashMap::new();
+    for (id, app) in apps.unwrap_or(&[]) {
+        let id = match id.parse() {
+            Ok(id) => id,
+            Err(_) => continue,
+        };
+
+        let tags = app.get("tags").map(AnyValue::entries).unwrap_or(&[]);
+        for (_, tag) in tags {
+            if let Some(tag) = tag.as_str() {
+                add(&mut out, id, tag);
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// The collections each app is in, by app id
+pub fn load_collections(steam_dir: &Path, user: &SteamUser) -> HashMap<u32, Vec<String>> {
+    let dir = user_dir(steam_dir, user);
+
+    let cloud = dir.join("config/cloudstorage/cloud-storage-namespace-1.json");
+    if cloud.exists() {
+        let collections = crate::attempt!(("read steam collections {:?}", cloud), {
+            parse_cloud_collections(&std::fs::read_to_string(&cloud)?)?
+        });
+
+        return collections.unwrap_or_default();
+    }
+
+    let shared = dir.join("7/remote/sharedconfig.vdf");
+    let tags = crate::attempt!(("read steam categories {:?}", shared), {
+        parse_shared_tags(&std::fs::read_to_string(&shared)?)?
+    });
+
+    tags.unwrap_or_default()
+}
+
+#[test]
+fn collections() -> Result<(), Box<dyn std::error::Error>> {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/steam");
+
+    let content = std::fs::read_to_string(fixtures.join("cloud-storage-namespace-1.json"))?;
+    let collections = parse_cloud_collections(&content)?;
+
+    assert_eq!(collections[&620], vec!["Favorites", "Co-op night"]);
+    assert_eq!(collections[&1145360], vec!["Backlog"]);
+    // added to co-op night and removed again
+    assert_eq!(collections.get(&440), None);
+
+    let content = std::fs::read_to_string(fixtures.join("sharedconfig.vdf"))?;
+    let tags = parse_shared_tags(&content)?;
+
+    assert_eq!(tags[&220], vec!["Half-Life"]);
+    assert_eq!(tags[&620], vec!["Co-op night", "favorite"]);
+    assert_eq!(tags.get(&400), None);
+
+    Ok(())
+}
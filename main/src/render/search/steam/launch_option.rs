@@ -0,0 +1,252 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use super::Scalar;
+use crate::render::search::split_arguments;
+
+// appinfo config.launch.<n>
+#[derive(Deserialize, Debug)]
+pub struct AppLaunch {
+    pub executable: String,
+    pub arguments: Option<Scalar>,
+    pub description: Option<String>,
+    #[serde(rename = "type")]
+    pub ty: Option<String>,
+    pub workingdir: Option<String>,
+    pub config: Option<AppLaunchConfig>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AppLaunchConfig {
+    pub oslist: Option<String>,
+    // only offered on that beta branch
+    pub betakey: Option<String>,
+}
+
+/// One of the entries steam asks to choose between when a game has several
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LaunchOption {
+    // position in the app's launch list, which is what identifies it
+    pub index: u32,
+    pub description: String,
+    pub executable: PathBuf,
+    pub arguments: String,
+    pub working_dir: PathBuf,
+}
+
+fn type_description(ty: &str) -> Option<&'static str> {
+    match ty {
+        "default" => Some("Play"),
+        "server" => Some("Server"),
+        "editor" => Some("Editor"),
+        "manual" => Some("Manual"),
+        "vr" | "othervr" => Some("VR"),
+        _ => None,
+    }
+}
+
+/// The launch options that run on windows, with paths resolved against the install dir
+pub fn windows_launch_options(launch: &[AppLaunch], install_dir: &Path) -> Vec<LaunchOption> {
+    let for_windows = |launch: &AppLaunch| match &launch.config {
+        Some(config) => {
+            let oslist = config.oslist.as_deref().unwrap_or("");
+            let windows = oslist.is_empty() || oslist.split(',').any(|os| os.trim() == "windows");
+            windows && config.betakey.is_none()
+        }
+        None => true,
+    };
+
+    launch
+        .iter()
+        .enumerate()
+        .filter(|(_, launch)| for_windows(launch) && launch.ty.as_deref() != Some("none"))
+        .map(|(index, launch)| {
+            let executable = install_dir.join(&launch.executable);
+
+            let description = launch.description.as_deref().filter(|d| !d.is_empty());
+            let description = description
+                .or_else(|| launch.ty.as_deref().and_then(type_description))
+                .map(str::to_owned)
+                .or_else(|| Some(executable.file_stem()?.to_string_lossy().into_owned()))
+                .unwrap_or_else(|| launch.executable.clone());
+
+            let working_dir = match &launch.workingdir {
+                Some(dir) if !dir.is_empty() => install_dir.join(dir),
+                _ => executable.parent().unwrap_or(install_dir).to_owned(),
+            };
+
+            LaunchOption {
+                index: index as u32,
+                description,
+                executable,
+                arguments: launch
+                    .arguments
+                    .clone()
+                    .map(String::from)
+                    .unwrap_or_default(),
+                working_dir,
+            }
+        })
+        .collect()
+}
+
+/// The arguments for steam.exe to start a game, or one of its launch options without the
+/// chooser. `default` is the option -applaunch starts. Steam adds the user's own launch
+/// options, `%command%` included
+pub fn steam_arguments(
+    app_id: u32,
+    option: Option<&LaunchOption>,
+    default: Option<&LaunchOption>,
+    arguments: &[String],
+) -> Vec<String> {
+    let mut args = vec!["-applaunch".into(), app_id.to_string()];
+
+    if let Some(option) = option {
+        let url = vec![format!("steam://launch/{}/option{}", app_id, option.index)];
+
+        // the launch url can't take arguments, -applaunch only stands in for the option when
+        // it starts the same executable with nothing but the option's arguments added
+        match default {
+            _ if arguments.is_empty() => return url,
+            Some(default) if default.index == option.index => {}
+            Some(default)
+                if default.executable == option.executable && default.arguments.is_empty() =>
+            {
+                args.extend(split_arguments(&option.arguments))
+            }
+            _ => {
+                crate::log!(
+                    "steam can't pass arguments to {:?} of {}, starting it without {:?}",
+                    option.description,
+                    app_id,
+                    arguments
+                );
+                return url;
+            }
+        }
+    }
+
+    args.extend(arguments.iter().cloned());
+    args
+}
+
+/// The launch option last picked for each game, so launching the game itself skips the chooser
+pub struct PreferredOptions {
+    path: PathBuf,
+    options: HashMap<u32, u32>,
+}
+
+impl PreferredOptions {
+    pub fn load(path: PathBuf) -> PreferredOptions {
+        let options = crate::attempt!(("open steam launch options {:?}", path), {
+            let src = BufReader::new(File::open(&path)?);
+            serde_json::from_reader(src)?
+        });
+
+        PreferredOptions {
+            path,
+            options: options.unwrap_or_default(),
+        }
+    }
+
+    pub fn get(&self, app_id: u32) -> Option<u32> {
+        self.options.get(&app_id).cloned()
+    }
+
+    pub fn set(&mut self, app_id: u32, index: u32) {
+        if self.options.insert(app_id, index) == Some(index) {
+            return;
+        }
+
+        crate::attempt!(("save steam launch options {:?}", self.path), {
+            let dst = File::create(&self.path)?;
+            serde_json::to_writer(dst, &self.options)?;
+        });
+    }
+}
+
+#[test]
+fn launch_options() -> Result<(), Box<dyn std::error::Error>> {
+    #[derive(Deserialize)]
+    struct Config {
+        launch: Vec<AppLaunch>,
+    }
+
+    let config: Config = super::vdf_text::from_str(
+        r#"
+        "config"
+        {
+            "launch"
+            {
+                "0" { "executable" "bin/x64/Game.exe" "type" "default" "config" { "oslist" "windows" } }
+                "1" { "executable" "bin/x64/Game.exe" "arguments" "-modded" "description" "Launch REDmod"
+                      "config" { "oslist" "windows" } }
+                "2" { "executable" "game.sh" "config" { "oslist" "linux" } }
+                "3" { "executable" "bin/x64/Game.exe" "arguments" "-dx12" "description" "Launch DX12"
+                      "config" { "oslist" "windows" "betakey" "experimental" } }
+                "4" { "executable" "tools/Server.exe" "workingdir" "tools/data" "type" "server" }
+            }
+        }
+        "#,
+    )
+    .map(|root: HashMap<String, Config>| root.into_iter().next().unwrap().1)?;
+
+    let install_dir = Path::new("games/Game");
+    let options = windows_launch_options(&config.launch, install_dir);
+
+    let described: Vec<_> = options
+        .iter()
+        .map(|o| (o.index, o.description.as_str(), o.arguments.as_str()))
+        .collect();
+    assert_eq!(
+        described,
+        vec![
+            (0, "Play", ""),
+            (1, "Launch REDmod", "-modded"),
+            (4, "Server", "")
+        ]
+    );
+
+    assert_eq!(options[1].executable, install_dir.join("bin/x64/Game.exe"));
+    assert_eq!(options[1].working_dir, install_dir.join("bin/x64"));
+    assert_eq!(options[2].working_dir, install_dir.join("tools/data"));
+
+    let novid = ["-novid".to_owned()];
+    let default = options.first();
+    assert_eq!(
+        steam_arguments(620, None, default, &[]),
+        vec!["-applaunch", "620"]
+    );
+    assert_eq!(
+        steam_arguments(620, None, default, &novid),
+        vec!["-applaunch", "620", "-novid"]
+    );
+    assert_eq!(
+        steam_arguments(620, Some(&options[0]), default, &novid),
+        vec!["-applaunch", "620", "-novid"]
+    );
+    assert_eq!(
+        steam_arguments(620, Some(&options[1]), default, &[]),
+        vec!["steam://launch/620/option1"]
+    );
+    // the default option runs the same executable, so only the arguments differ
+    assert_eq!(
+        steam_arguments(620, Some(&options[1]), default, &novid),
+        vec!["-applaunch", "620", "-modded", "-novid"]
+    );
+    // -applaunch would start the game instead of the server, so the arguments are left out
+    assert_eq!(
+        steam_arguments(620, Some(&options[2]), default, &novid),
+        vec!["steam://launch/620/option4"]
+    );
+    assert_eq!(
+        steam_arguments(620, Some(&options[2]), None, &novid),
+        vec!["steam://launch/620/option4"]
+    );
+
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use std::path::{Path, PathBuf};
+
+// appcache/librarycache holds the artwork steam shows in its library.
+// older clients name the files <appid>_<kind>.jpg, newer ones use <appid>/<kind>.jpg
+// and name the icon after its hash
+pub struct LibraryCache {
+    dir: PathBuf,
+}
+
+impl LibraryCache {
+    pub fn new(steam_dir: &Path) -> LibraryCache {
+        let dir = steam_dir.join("appcache/librarycache");
+        LibraryCache { dir }
+    }
+
+    fn file(&self, app_id: u32, kind: &str) -> Option<PathBuf> {
+        let candidates = [
+            self.dir.join(format!("{}_{}.jpg", app_id, kind)),
+            self.dir
+                .join(app_id.to_string())
+                .join(format!("{}.jpg", kind)),
+        ];
+
+        candidates.iter().find(|p| p.is_file()).cloned()
+    }
+
+    pub fn icon(&self, app_id: u32) -> Option<PathBuf> {
+        if let Some(icon) = self.file(app_id, "icon") {
+            return Some(icon);
+        }
+
+        let is_hash = |p: &Path| match p.file_stem().and_then(|s| s.to_str()) {
+            Some(stem) => stem.len() == 40 && stem.chars().all(|c| c.is_ascii_hexdigit()),
+            None => false,
+        };
+
+        let entries = self.dir.join(app_id.to_string()).read_dir().ok()?;
+        entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .find(|p| is_hash(p) && p.extension().and_then(|e| e.to_str()) == Some("jpg"))
+    }
+
+    // the 600x900 cover
+    pub fn capsule(&self, app_id: u32) -> Option<PathBuf> {
+        self.file(app_id, "library_600x900")
+    }
+
+    pub fn header(&self, app_id: u32) -> Option<PathBuf> {
+        self.file(app_id, "header")
+    }
+
+    pub fn hero(&self, app_id: u32) -> Option<PathBuf> {
+        self.file(app_id, "library_hero")
+    }
+}
+
+#[test]
+fn library_cache() -> std::io::Result<()> {
+    let steam_dir = std::env::temp_dir().join(format!("library-cache-{}", std::process::id()));
+    let cache = steam_dir.join("appcache/librarycache");
+    std::fs::create_dir_all(cache.join("1145360"))?;
+
+    std::fs::write(cache.join("620_icon.jpg"), b"")?;
+    std::fs::write(cache.join("620_library_600x900.jpg"), b"")?;
+    std::fs::write(cache.join("620_library_hero.jpg"), b"")?;
+
+    let hash = "0123456789abcdef0123456789abcdef01234567";
+    std::fs::write(cache.join("1145360").join(format!("{}.jpg", hash)), b"")?;
+    std::fs::write(cache.join("1145360/header.jpg"), b"")?;
+    std::fs::write(cache.join("1145360/logo.png"), b"")?;
+
+    let library = LibraryCache::new(&steam_dir);
+    assert_eq!(library.icon(620), Some(cache.join("620_icon.jpg")));
+    assert_eq!(
+        library.capsule(620),
+        Some(cache.join("620_library_600x900.jpg"))
+    );
+    assert_eq!(library.hero(620), Some(cache.join("620_library_hero.jpg")));
+    assert_eq!(library.header(620), None);
+
+    let icon = cache.join("1145360").join(format!("{}.jpg", hash));
+    assert_eq!(library.icon(1145360), Some(icon));
+    assert_eq!(
+        library.header(1145360),
+        Some(cache.join("1145360/header.jpg"))
+    );
+    assert_eq!(library.capsule(1145360), None);
+    assert_eq!(library.icon(440), None);
+
+    Ok(())
+}
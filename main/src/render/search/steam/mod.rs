@@ -1,42 +1,45 @@
 use std::{
-    collections::HashMap, fs::File, io::prelude::*, io::Cursor, path::Path, path::PathBuf,
-    process::Command,
+    cell::RefCell, collections::HashMap, collections::HashSet, fs::File, hash::Hash, hash::Hasher,
+    io::prelude::*, io::Cursor, path::Path, path::PathBuf, process::Command, rc::Rc,
+    time::SystemTime,
 };
 
-use flat::prelude::*;
 use image::{ico::IcoDecoder, DynamicImage};
-use serde::Deserialize;
 
 mod vdf_text;
-use vdf_text::{key_value, AnyValue};
+use vdf_text::AnyValue;
 
 mod vdf_binary;
-use vdf_binary::ValveDeserializer;
 
-use crate::common::extract_icons;
+mod appinfo;
+use appinfo::AppInfoFile;
 
-use super::SearchProvider;
+mod app_state;
+use app_state::{AppManifest, InstallState};
 
-flat_data!(AppInfoHeader);
-#[repr(C, packed)]
-#[derive(Copy, Clone)]
-struct AppInfoHeader {
-    magic: u32,
-    universe: u32,
-}
+mod launch_option;
+use launch_option::{
+    steam_arguments, windows_launch_options, AppLaunch, LaunchOption, PreferredOptions,
+};
 
-flat_data!(AppInfoEntryHeader);
-#[repr(C, packed)]
-#[derive(Copy, Clone)]
-struct AppInfoEntryHeader {
-    app_id: u32,
-    size: u32,
-    state: u32,
-    last_updated: u32,
-    pics_token: u64,
-    sha1: [u8; 20],
-    change_number: u32,
-}
+mod library_cache;
+use library_cache::LibraryCache;
+
+mod user;
+use user::{
+    active_account_id, describe_last_played, describe_playtime, load_known_apps, load_user_apps,
+    load_users, merge_user_apps, select_users, AccountSelection, SteamUser, UserAppData,
+};
+
+mod collections;
+use collections::{load_collections, merge_collections};
+
+mod shortcuts;
+pub use shortcuts::{ExportShortcut, SteamShortcutProvider, SteamShortcutTarget};
+
+use crate::common::{extract_icons, focus_running, RunningMatch};
+
+use super::{Artwork, LaunchOptions, SearchProvider};
 
 #[derive(Deserialize, Debug)]
 struct AppInfoEntry {
@@ -63,20 +66,8 @@ struct AppInfoConfig {
     launch: Option<Vec<AppLaunch>>,
 }
 
-#[derive(Deserialize, Debug)]
-struct AppLaunch {
-    executable: String,
-    arguments: Option<Scalar>,
-    config: Option<AppLaunchConfig>,
-}
-
-#[derive(Deserialize, Debug)]
-struct AppLaunchConfig {
-    oslist: Option<String>,
-}
-
 #[serde(untagged)]
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 enum Scalar {
     I32(i32),
     I64(i64),
@@ -100,18 +91,239 @@ impl From<Scalar> for String {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SteamConfig {
     path: String,
+    // the account to show games and data for, by account or persona name, or `all` to merge
+    // every account that has logged in. defaults to the one steam is logged in to
+    #[serde(default)]
+    account: Option<String>,
+    // also list the games that aren't installed but have local or cloud settings, launching
+    // them installs them. these aren't the games an account owns, which can't be read: ones
+    // never started are missing and ones it no longer has can be there (see load_known_apps)
+    #[serde(default, alias = "show_uninstalled")]
+    show_uninstalled_with_settings: bool,
+    // app types from appinfo.vdf to list (game, application, tool, demo, dlc, config)
+    #[serde(default = "default_app_types")]
+    app_types: Vec<String>,
+    #[serde(default)]
+    exclude_app_types: Vec<String>,
+    #[serde(default)]
+    exclude_apps: Vec<u32>,
+    // rank games that are updating or need an update below ones that can start right away
+    #[serde(default)]
+    demote_unavailable: bool,
+}
+
+// hides redistributables (type config) and tools like proton, steamvr and dedicated servers
+fn default_app_types() -> Vec<String> {
+    vec!["game".into(), "application".into(), "demo".into()]
+}
+
+#[derive(Debug, Clone)]
+struct AppFilter {
+    // empty allows every type
+    include: Vec<String>,
+    exclude: Vec<String>,
+    exclude_apps: HashSet<u32>,
+}
+
+impl AppFilter {
+    fn new(config: &SteamConfig) -> AppFilter {
+        AppFilter {
+            include: config.app_types.clone(),
+            exclude: config.exclude_app_types.clone(),
+            exclude_apps: config.exclude_apps.iter().cloned().collect(),
+        }
+    }
+
+    // apps missing from appinfo.vdf have no type, they are kept so nothing disappears
+    // when it can't be read
+    fn allows(&self, app_id: u32, ty: Option<&str>) -> bool {
+        if self.exclude_apps.contains(&app_id) {
+            return false;
+        }
+
+        let ty = match ty {
+            Some(ty) => ty,
+            None => return true,
+        };
+
+        let listed = |list: &[String]| list.iter().any(|t| t.eq_ignore_ascii_case(ty));
+        (self.include.is_empty() || listed(&self.include)) && !listed(&self.exclude)
+    }
 }
 
 pub struct SteamProvider {
     steam_dir: PathBuf,
     app_info: HashMap<u32, AppInfo>,
+    // from the localconfig.vdf of the most recent user
+    user_apps: HashMap<u32, UserAppData>,
+    collections: HashMap<u32, Vec<String>>,
+    // the selected users, and the names of the ones that know each app when they are merged
+    users: Vec<SteamUser>,
+    accounts: Option<HashMap<u32, Vec<String>>>,
+    library_cache: LibraryCache,
+    // games to list when they aren't installed, empty unless `show_uninstalled_with_settings`
+    // is set
+    with_settings: HashSet<u32>,
+    filter: AppFilter,
+    demote_unavailable: bool,
+    // shared with the launch closures, which record the option picked
+    preferred: Rc<RefCell<PreferredOptions>>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SteamTarget {
     app_id: u32,
     name: String,
     install_dir: PathBuf,
+    #[serde(default)]
+    library_label: String,
+    #[serde(default)]
+    last_played: u64,
+    #[serde(default)]
+    playtime: u32,
+    #[serde(default)]
+    launch_options: String,
+    #[serde(default = "default_installed")]
+    installed: bool,
+    #[serde(default)]
+    install_state: InstallState,
+    // set for the entries listed under a game for each of its launch options
+    #[serde(default)]
+    launch_option: Option<LaunchOption>,
+    // names of the user's collections the game is in
+    #[serde(default)]
+    collections: Vec<String>,
+    // the accounts that know the game, only when showing every account
+    #[serde(default)]
+    accounts: Vec<String>,
+}
+
+fn default_installed() -> bool {
+    true
+}
+
+impl SteamTarget {
+    pub fn app_id(&self) -> u32 {
+        self.app_id
+    }
+
+    fn option_index(&self) -> Option<u32> {
+        self.launch_option.as_ref().map(|option| option.index)
+    }
+}
+
+// a target is identified by its app id and launch option, everything else is refreshed
+// when the index is built
+impl PartialEq for SteamTarget {
+    fn eq(&self, other: &Self) -> bool {
+        self.app_id == other.app_id && self.option_index() == other.option_index()
+    }
+}
+
+impl Eq for SteamTarget {}
+
+impl Hash for SteamTarget {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.app_id.hash(state);
+        self.option_index().hash(state);
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SteamLibrary {
+    path: PathBuf,
+    label: String,
+    // app ids installed in this library, only known with the newer libraryfolders.vdf layout
+    apps: Option<HashSet<u32>>,
+}
+
+fn parse_library_folders(
+    steam_dir: &Path,
+    content: &str,
+) -> Result<Vec<SteamLibrary>, Box<dyn std::error::Error>> {
+    let root = vdf_text::parse(content)?;
+
+    let map = match root.entries().first() {
+        Some((_, map @ AnyValue::Map(_))) => map,
+        _ => return Err("libraryfolders root is not an object".into()),
+    };
+
+    // libraries are numbered keys, other keys are stats and ids
+    let mut numbered: Vec<_> = map
+        .entries()
+        .iter()
+        .filter_map(|(key, value)| Some((key.parse::<u32>().ok()?, value)))
+        .collect();
+    numbered.sort_by_key(|(index, _)| *index);
+
+    let mut collect = vec![];
+    for (_, value) in numbered {
+        let library = match value {
+            // "1" "D:\\Games"
+            AnyValue::String(p) => SteamLibrary {
+                path: PathBuf::from(p),
+                label: String::new(),
+                apps: None,
+            },
+            // "1" { "path" "D:\\SteamLibrary" "label" "" "apps" { ... } }
+            library @ AnyValue::Map(_) => {
+                let path = match library.get_str("path") {
+                    Some(p) => PathBuf::from(p),
+                    None => continue,
+                };
+
+                let label = library.get_str("label").unwrap_or("").to_owned();
+
+                let apps = match library.get("apps") {
+                    Some(apps @ AnyValue::Map(_)) => Some(
+                        apps.entries()
+                            .iter()
+                            .filter_map(|(id, _)| id.parse().ok())
+                            .collect(),
+                    ),
+                    _ => None,
+                };
+
+                SteamLibrary { path, label, apps }
+            }
+        };
+
+        collect.push(library);
+    }
+
+    // the old layout leaves out the main steam folder, which is always a library
+    let is_main = |l: &SteamLibrary| same_path(&l.path, steam_dir);
+    if !collect.iter().any(is_main) {
+        let main = SteamLibrary {
+            path: steam_dir.to_owned(),
+            label: String::new(),
+            apps: None,
+        };
+
+        collect.insert(0, main);
+    }
+
+    Ok(collect)
+}
+
+fn same_path(a: &Path, b: &Path) -> bool {
+    let normalize = |p: &Path| {
+        let lower = p.to_string_lossy().to_lowercase().replace('/', "\\");
+        lower.trim_end_matches('\\').to_owned()
+    };
+
+    normalize(a) == normalize(b)
+}
+
+// the accounts chosen by `account`, empty if none have logged in
+fn selected_users(config: &SteamConfig) -> Vec<SteamUser> {
+    let steam_dir = Path::new(&config.path);
+    let users = crate::attempt!(("read steam users {:?}", steam_dir), {
+        load_users(steam_dir)?
+    });
+
+    let selection = AccountSelection::from_config(config.account.as_deref());
+    select_users(&users.unwrap_or_default(), &selection, active_account_id())
 }
 
 impl SteamProvider {
@@ -123,145 +335,423 @@ impl SteamProvider {
             let mut content = vec![];
             File::open(appinfo)?.read_to_end(&mut content)?;
 
-            let mut content: &[u8] = content.as_ref();
-            let _header: &AppInfoHeader = content.load();
+            let file = AppInfoFile::parse(&content)?;
 
+            // one app steam wrote in a way that isn't understood shouldn't hide the others
             let mut app_info = HashMap::new();
-            while content.len() > 4 {
-                let entry: &AppInfoEntryHeader = content.load();
-
-                let mut deserializer = ValveDeserializer::new(&mut content);
+            for record in file.records()? {
+                let entry = crate::attempt!(("read appinfo of {}", record.app_id), {
+                    record.deserialize::<AppInfoEntry>()?
+                });
 
-                let x: AppInfoEntry = Deserialize::deserialize(&mut deserializer)?;
-                app_info.insert(entry.app_id, x.appinfo);
+                if let Some(entry) = entry {
+                    app_info.insert(record.app_id, entry.appinfo);
+                }
             }
 
             app_info
         })
         .unwrap_or_default();
 
+        let selection = AccountSelection::from_config(config.account.as_deref());
+        let users = selected_users(config);
+
+        let mut user_apps = HashMap::new();
+        let mut collections = HashMap::new();
+        let mut with_settings = HashSet::new();
+        let mut accounts = match selection {
+            AccountSelection::All => Some(HashMap::new()),
+            _ => None,
+        };
+
+        for user in &users {
+            let apps = crate::attempt!(("read steam user data {:?}", user.account_name), {
+                load_user_apps(steam_dir, user)?
+            });
+            let apps = apps.unwrap_or_default();
+
+            merge_collections(&mut collections, load_collections(steam_dir, user));
+
+            let known = load_known_apps(steam_dir, user, &apps);
+            if let Some(accounts) = &mut accounts {
+                for &app_id in &known {
+                    let names: &mut Vec<_> = accounts.entry(app_id).or_default();
+                    names.push(user.account_name.clone());
+                }
+            }
+
+            if config.show_uninstalled_with_settings {
+                with_settings.extend(known);
+            }
+
+            merge_user_apps(&mut user_apps, apps);
+        }
+
         SteamProvider {
             steam_dir: steam_dir.to_owned(),
             app_info,
+            user_apps,
+            collections,
+            users,
+            accounts,
+            library_cache: LibraryCache::new(steam_dir),
+            with_settings,
+            filter: AppFilter::new(config),
+            demote_unavailable: config.demote_unavailable,
+            preferred: Rc::new(RefCell::new(PreferredOptions::load(
+                "steam_launch_options.json".into(),
+            ))),
         }
     }
 
-    fn get_library_paths(&self) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    /// Describe the target as a shortcut that has steam launch the game or option, or install it
+    pub fn shortcut(&self, entry: &SteamTarget) -> ExportShortcut {
+        let steam_exe = self.steam_dir.join("steam.exe");
+        let launch_options = match (entry.installed, &entry.launch_option) {
+            (true, None) => format!("-applaunch {}", entry.app_id),
+            (true, Some(option)) => {
+                format!("steam://launch/{}/option{}", entry.app_id, option.index)
+            }
+            (false, _) => format!("steam://install/{}", entry.app_id),
+        };
+        let name = match &entry.launch_option {
+            Some(option) => format!("{} ({})", entry.name, option.description),
+            None => entry.name.clone(),
+        };
+
+        ExportShortcut {
+            name,
+            exe: steam_exe.to_string_lossy().into_owned(),
+            start_dir: self.steam_dir.to_string_lossy().into_owned(),
+            launch_options,
+            icon: steam_exe.to_string_lossy().into_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn get_libraries(&self) -> Result<Vec<SteamLibrary>, Box<dyn std::error::Error>> {
         let libraryfolders = self.steam_dir.join("steamapps/libraryfolders.vdf");
 
         let mut content = String::new();
         File::open(libraryfolders)?.read_to_string(&mut content)?;
 
-        let (_, (_, value)) = key_value(content.trim())
-            .map_err(|x| format!("failed to parse libraryfolders: {:?}", x))?;
-
-        let map = match value {
-            AnyValue::Map(a) => a,
-            _ => panic!(),
-        };
-
-        let mut collect = vec![];
-        let mut index = 1;
-        while map.contains_key(&index.to_string()) {
-            match &map[&index.to_string()] {
-                AnyValue::String(p) => collect.push(PathBuf::from(p)),
-                _ => (),
-            };
-            index += 1;
-        }
-
-        Ok(collect)
+        parse_library_folders(&self.steam_dir, &content)
     }
 
     fn get_apps(
         &self,
-        library_path: &Path,
+        library: &SteamLibrary,
     ) -> Result<Vec<SteamTarget>, Box<dyn std::error::Error>> {
-        let steam_apps = library_path.join("steamapps");
+        let steam_apps = library.path.join("steamapps");
         let common = steam_apps.join("common");
 
-        let mut out = vec![];
+        // libraries that list their apps don't need a directory scan to find the manifests
+        let manifests: Vec<_> = match &library.apps {
+            Some(apps) => apps
+                .iter()
+                .map(|id| steam_apps.join(format!("appmanifest_{}.acf", id)))
+                .filter(|path| path.exists())
+                .collect(),
+            None => steam_apps
+                .read_dir()?
+                .filter_map(|entry| Some(entry.ok()?.path()))
+                .filter(|path| path.extension().and_then(|e| e.to_str()) == Some("acf"))
+                .collect(),
+        };
 
-        for entry in steam_apps.read_dir()? {
-            let entry = entry?;
-            let path = entry.path();
+        let mut out = vec![];
 
-            if let Some("acf") = path.extension().and_then(|e| e.to_str()) {
+        for path in manifests {
+            let manifest = crate::attempt!(("read app manifest {:?}", path), {
                 let mut content = String::new();
                 File::open(&path)?.read_to_string(&mut content)?;
+                vdf_text::from_str::<AppManifest>(&content)?
+            });
 
-                let (_, (_, value)) = key_value(content.trim())
-                    .map_err(|x| format!("failed to parse libraryfolders: {:?}", x))?;
+            let state = match manifest {
+                Some(manifest) => manifest.app_state,
+                None => continue,
+            };
 
-                let map = match value {
-                    AnyValue::Map(a) => a,
-                    _ => panic!(),
-                };
+            let user = self.user_apps.get(&state.app_id).cloned();
+            let user = user.unwrap_or_default();
+            let install_state = InstallState::from_manifest(&state);
+
+            out.push(SteamTarget {
+                app_id: state.app_id,
+                name: state.name,
+                install_dir: common.join(state.install_dir),
+                library_label: library.label.clone(),
+                last_played: user.last_played,
+                playtime: user.playtime,
+                launch_options: user.launch_options,
+                installed: true,
+                install_state,
+                launch_option: None,
+                collections: self.get_collections(state.app_id),
+                accounts: self.get_accounts(state.app_id, state.last_owner),
+            });
+        }
 
-                let app_id = match &map["appid"] {
-                    AnyValue::String(s) => s.parse()?,
-                    _ => continue,
-                };
+        Ok(out)
+    }
 
-                let name = match &map["name"] {
-                    AnyValue::String(s) => s.clone(),
-                    _ => continue,
-                };
+    fn app_type(&self, app_id: u32) -> Option<&str> {
+        let info = self.app_info.get(&app_id)?;
+        Some(info.common.as_ref()?.ty.as_str())
+    }
 
-                let install_dir = match &map["installdir"] {
-                    AnyValue::String(s) => s,
-                    _ => continue,
-                };
+    fn get_uninstalled(&self, app_id: u32) -> Option<SteamTarget> {
+        let info = self.app_info.get(&app_id)?;
+        let common = info.common.as_ref()?;
 
-                let install_dir = common.join(install_dir);
+        // settings are also kept for tools, dlc and soundtracks
+        let is_game = common.ty.eq_ignore_ascii_case("game");
+        if !is_game || !self.filter.allows(app_id, Some(&common.ty)) {
+            return None;
+        }
 
-                out.push(SteamTarget {
-                    app_id,
-                    name,
-                    install_dir,
-                });
-            }
+        let user = self.user_apps.get(&app_id).cloned().unwrap_or_default();
+
+        Some(SteamTarget {
+            app_id,
+            name: common.name.clone(),
+            install_dir: PathBuf::new(),
+            library_label: String::new(),
+            last_played: user.last_played,
+            playtime: user.playtime,
+            launch_options: user.launch_options,
+            installed: false,
+            install_state: InstallState::default(),
+            launch_option: None,
+            collections: self.get_collections(app_id),
+            accounts: self.get_accounts(app_id, 0),
+        })
+    }
+
+    fn get_collections(&self, app_id: u32) -> Vec<String> {
+        self.collections.get(&app_id).cloned().unwrap_or_default()
+    }
+
+    // the manifest of an installed game has the steam id of the account that installed it
+    fn get_accounts(&self, app_id: u32, last_owner: u64) -> Vec<String> {
+        let accounts = match &self.accounts {
+            Some(accounts) => accounts,
+            None => return vec![],
+        };
+
+        let mut names = accounts.get(&app_id).cloned().unwrap_or_default();
+        let owner = self.users.iter().find(|u| u.steam_id == last_owner);
+        if let Some(owner) = owner.filter(|u| !names.contains(&u.account_name)) {
+            names.push(owner.account_name.clone());
         }
 
-        Ok(out)
+        names
+    }
+
+    fn get_launch_options(&self, app_id: u32, install_dir: &Path) -> Vec<LaunchOption> {
+        let launch = self
+            .app_info
+            .get(&app_id)
+            .and_then(|info| info.config.as_ref()?.launch.as_ref());
+
+        match launch {
+            Some(launch) => windows_launch_options(launch, install_dir),
+            None => vec![],
+        }
+    }
+
+    // an entry for each launch option, when steam would ask which to use
+    fn get_option_targets(&self, app: &SteamTarget) -> Vec<SteamTarget> {
+        let options = self.get_launch_options(app.app_id, &app.install_dir);
+        if options.len() < 2 {
+            return vec![];
+        }
+
+        options
+            .into_iter()
+            .map(|option| SteamTarget {
+                launch_option: Some(option),
+                ..app.clone()
+            })
+            .collect()
     }
 }
 
 impl SearchProvider<SteamTarget> for SteamProvider {
     fn index(&self) -> Vec<SteamTarget> {
         let apps = self
-            .get_library_paths()
+            .get_libraries()
             .into_iter()
             .flatten()
-            .map(|p| self.get_apps(&p))
+            .map(|l| self.get_apps(&l))
             .flatten()
             .flatten();
 
-        apps.collect()
+        let mut apps: Vec<_> = apps
+            .filter(|app| self.filter.allows(app.app_id, self.app_type(app.app_id)))
+            .collect();
+        let installed: HashSet<_> = apps.iter().map(|app| app.app_id).collect();
+
+        let options: Vec<_> = apps
+            .iter()
+            .flat_map(|app| self.get_option_targets(app))
+            .collect();
+
+        let mut uninstalled: Vec<_> = self
+            .with_settings
+            .iter()
+            .filter(|id| !installed.contains(*id))
+            .filter_map(|&app_id| self.get_uninstalled(app_id))
+            .collect();
+        uninstalled.sort_by_key(|app| app.app_id);
+
+        apps.extend(options);
+        apps.extend(uninstalled);
+        apps
     }
 
     fn keys(&self, entry: &SteamTarget) -> Vec<String> {
-        vec![entry.name.clone()]
+        match &entry.launch_option {
+            Some(option) => vec![format!("{} \u{203a} {}", entry.name, option.description)],
+            None => vec![entry.name.clone()],
+        }
     }
 
-    fn launch(&self, entry: &SteamTarget) -> Box<dyn Fn()> {
+    fn launch(&self, entry: &SteamTarget) -> Box<dyn Fn(&LaunchOptions)> {
         let steam_exe = self.steam_dir.join("steam.exe");
         let app_id = entry.app_id;
 
-        Box::new(move || {
+        if !entry.installed {
+            return Box::new(move |_| {
+                Command::new(&steam_exe)
+                    .arg(format!("steam://install/{}", app_id))
+                    .spawn()
+                    .expect("spawn process");
+            });
+        }
+
+        let running = RunningMatch {
+            dir: Some(entry.install_dir.clone()),
+            ..Default::default()
+        };
+
+        let preferred = self.preferred.clone();
+        let mut choices = self.get_launch_options(app_id, &entry.install_dir);
+
+        if let Some(option) = &entry.launch_option {
+            let option = option.clone();
+            // what -applaunch starts
+            let default = choices.first().cloned();
+
+            return Box::new(move |options| {
+                preferred.borrow_mut().set(app_id, option.index);
+
+                if options.focus_running() && focus_running(&running) {
+                    return;
+                }
+
+                let args =
+                    steam_arguments(app_id, Some(&option), default.as_ref(), &options.arguments);
+                Command::new(&steam_exe)
+                    .args(args)
+                    .spawn()
+                    .expect("spawn process");
+            });
+        }
+
+        // a single option is what steam starts without asking anyway
+        if choices.len() < 2 {
+            choices.clear();
+        }
+
+        Box::new(move |options| {
+            if options.focus_running() && focus_running(&running) {
+                return;
+            }
+
+            // skip steam's chooser once an option has been picked from here
+            let index = preferred.borrow().get(app_id);
+            let option = choices.iter().find(|option| Some(option.index) == index);
+
             Command::new(&steam_exe)
-                .arg("-applaunch") //
-                .arg(&format!("{}", app_id)) //
+                .args(steam_arguments(
+                    app_id,
+                    option,
+                    choices.first(),
+                    &options.arguments,
+                ))
                 .spawn()
                 .expect("spawn process");
         })
     }
 
+    fn tags(&self, entry: &SteamTarget) -> Vec<String> {
+        let accounts = entry.accounts.iter();
+        entry.collections.iter().chain(accounts).cloned().collect()
+    }
+
     fn details(&self, entry: &SteamTarget) -> String {
-        format!(r"Steam: {}", entry.app_id)
+        let mut details = match entry.library_label.as_str() {
+            _ if !entry.installed => format!(r"Steam: {} (not installed)", entry.app_id),
+            "" => format!(r"Steam: {}", entry.app_id),
+            label => format!(r"Steam: {} ({})", entry.app_id, label),
+        };
+
+        if let Some(option) = &entry.launch_option {
+            let executable = option.executable.strip_prefix(&entry.install_dir);
+            let executable = executable.unwrap_or(&option.executable);
+            details += &format!(", {}", executable.display());
+            if option.arguments.len() > 0 {
+                details += &format!(" {}", option.arguments);
+            }
+        }
+
+        if let Some(state) = entry.install_state.describe() {
+            details += &format!(", {}", state);
+        }
+
+        if entry.playtime > 0 {
+            details += &format!(", {} played", describe_playtime(entry.playtime));
+        }
+
+        if entry.last_played > 0 {
+            let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH);
+            let now = now.map(|d| d.as_secs()).unwrap_or(0);
+            details += &format!(", last {}", describe_last_played(entry.last_played, now));
+        }
+
+        if entry.launch_options.len() > 0 {
+            details += &format!(", {}", entry.launch_options);
+        }
+
+        details
+    }
+
+    // a rough log scale of the hours played, so a long played game doesn't outrank
+    // something launched from here a few times
+    fn seed_counter(&self, entry: &SteamTarget) -> u64 {
+        // launch options start below the game itself
+        if entry.launch_option.is_some() {
+            return 0;
+        }
+
+        match entry.playtime {
+            0 => 0,
+            minutes => 1 + (64 - (minutes as u64 / 60).leading_zeros() as u64),
+        }
     }
 
     fn display_icon(&self, entry: &SteamTarget) -> Option<image::DynamicImage> {
+        // the library artwork covers games whose exe has no icon, or that launch through a stub
+        if let Some(icon) = self.library_cache.icon(entry.app_id) {
+            if let Some(image) =
+                crate::attempt!(("open steam icon {:?}", icon), image::open(&icon)?)
+            {
+                return Some(image);
+            }
+        }
+
         self.app_info
             .get(&entry.app_id)
             .and_then(|x| Some(x.config.as_ref()?.launch.as_ref()?.iter()))
@@ -289,39 +779,127 @@ impl SearchProvider<SteamTarget> for SteamProvider {
             })
             .next()
     }
+
+    fn is_installed(&self, entry: &SteamTarget) -> bool {
+        entry.installed
+    }
+
+    fn demoted(&self, entry: &SteamTarget) -> bool {
+        self.demote_unavailable && !(entry.installed && entry.install_state.can_launch())
+    }
+
+    fn artwork(&self, entry: &SteamTarget, kind: Artwork) -> Option<PathBuf> {
+        match kind {
+            Artwork::Capsule => self.library_cache.capsule(entry.app_id),
+            Artwork::Header => self.library_cache.header(entry.app_id),
+            Artwork::Hero => self.library_cache.hero(entry.app_id),
+        }
+    }
 }
 
+// dumps the appinfo.vdf of a local steam install
 #[test]
+#[ignore]
 fn test() -> Result<(), Box<dyn std::error::Error>> {
-    use self::vdf_binary::{ValveReader, ValveToken};
+    use self::vdf_binary::ValveToken;
 
     let steam_dir = Path::new(r"C:\Program Files (x86)\Steam");
     let appinfo = steam_dir.join("appcache/appinfo.vdf");
     let mut content = vec![];
     File::open(appinfo)?.read_to_end(&mut content)?;
 
-    let mut content: &[u8] = content.as_ref();
-    let _header: &AppInfoHeader = content.load();
+    let file = AppInfoFile::parse(&content)?;
+    println!("{:?}", file.version);
 
-    while content.len() > 4 {
-        let _entry: &AppInfoEntryHeader = content.load();
+    for record in file.records()? {
+        record.visit(|reader| {
+            let mut indent = String::new();
 
-        let reader = ValveReader::new(&mut content);
-        let mut indent = String::new();
+            for node in reader {
+                let node = match node {
+                    Ok(node) => node,
+                    Err(e) => return println!("{}", e),
+                };
 
-        for node in reader {
-            match node {
-                None => indent.truncate(indent.len() - 2),
-                Some((key, ValveToken::Object)) => {
-                    println!("{}{}:", indent, key);
-                    indent += "  ";
-                }
-                Some((key, value)) => {
-                    println!("{}{}: {:?}", indent, key, value);
+                match node {
+                    None => indent.truncate(indent.len() - 2),
+                    Some((key, ValveToken::Object)) => {
+                        println!("{}{}:", indent, key);
+                        indent += "  ";
+                    }
+                    Some((key, value)) => {
+                        println!("{}{}: {:?}", indent, key, value);
+                    }
                 }
             }
-        }
+        });
     }
 
     Ok(())
 }
+
+#[test]
+fn app_filter() {
+    let config: SteamConfig = serde_yaml::from_str("{ path: '', exclude_apps: [440] }").unwrap();
+    let filter = AppFilter::new(&config);
+
+    assert!(filter.allows(620, Some("Game")));
+    assert!(filter.allows(1007, Some("application")));
+    assert!(!filter.allows(228980, Some("Config")));
+    assert!(!filter.allows(1493710, Some("Tool")));
+    assert!(!filter.allows(440, Some("Game")));
+    assert!(filter.allows(12345, None));
+
+    let config: SteamConfig =
+        serde_yaml::from_str("{ path: '', app_types: [], exclude_app_types: [dlc] }").unwrap();
+    let filter = AppFilter::new(&config);
+
+    assert!(filter.allows(1493710, Some("tool")));
+    assert!(!filter.allows(1234, Some("DLC")));
+}
+
+#[test]
+fn library_folders_old_layout() -> Result<(), Box<dyn std::error::Error>> {
+    let fixture =
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/steam/libraryfolders_old.vdf");
+    let content = std::fs::read_to_string(fixture)?;
+
+    let steam_dir = Path::new(r"C:\Program Files (x86)\Steam");
+    let libraries = parse_library_folders(steam_dir, &content)?;
+
+    let paths: Vec<_> = libraries.iter().map(|l| l.path.to_str().unwrap()).collect();
+    assert_eq!(
+        paths,
+        [
+            r"C:\Program Files (x86)\Steam",
+            r"D:\Games\Steam",
+            r"E:\SteamLibrary"
+        ]
+    );
+    assert!(libraries.iter().all(|l| l.apps.is_none() && l.label == ""));
+
+    Ok(())
+}
+
+#[test]
+fn library_folders_new_layout() -> Result<(), Box<dyn std::error::Error>> {
+    let fixture =
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/steam/libraryfolders_new.vdf");
+    let content = std::fs::read_to_string(fixture)?;
+
+    let steam_dir = Path::new(r"C:\Program Files (x86)\Steam\");
+    let libraries = parse_library_folders(steam_dir, &content)?;
+
+    assert_eq!(libraries.len(), 2);
+    assert_eq!(
+        libraries[0].path,
+        Path::new(r"C:\Program Files (x86)\Steam")
+    );
+    assert_eq!(libraries[1].path, Path::new(r"D:\SteamLibrary"));
+    assert_eq!(libraries[1].label, "SSD");
+
+    let apps = libraries[1].apps.as_ref().unwrap();
+    assert!(apps.contains(&620) && apps.contains(&1145360) && apps.len() == 2);
+
+    Ok(())
+}
@@ -0,0 +1,528 @@
+use std::{
+    fs::File, hash::Hash, hash::Hasher, io::prelude::*, io::Cursor, path::Path, path::PathBuf,
+    process::Command, time::SystemTime,
+};
+
+use image::{ico::IcoDecoder, DynamicImage};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::Deserialize;
+
+use super::{
+    selected_users,
+    user::{user_dir, SteamUser},
+    vdf_binary::{to_vec, ValveDeserializer, ValveError, ValveValue},
+    LaunchOptions, SearchProvider, SteamConfig,
+};
+use crate::common::{extract_icons, focus_running, join_arguments, RunningMatch};
+
+#[derive(Deserialize, Debug)]
+struct ShortcutsFile {
+    // keyed "0", "1", ... "10", read in the order steam lists them
+    shortcuts: Vec<Shortcut>,
+}
+
+// older clients write some of the keys in lower case
+#[derive(Deserialize, Debug)]
+struct Shortcut {
+    appid: Option<i32>,
+    #[serde(rename = "AppName", alias = "appname")]
+    app_name: String,
+    #[serde(rename = "Exe", alias = "exe")]
+    exe: String,
+    #[serde(rename = "StartDir")]
+    start_dir: Option<String>,
+    icon: Option<String>,
+    #[serde(rename = "LaunchOptions")]
+    launch_options: Option<String>,
+    #[serde(rename = "IsHidden")]
+    is_hidden: Option<i32>,
+}
+
+pub struct SteamShortcutProvider {
+    steam_dir: PathBuf,
+    users: Vec<SteamUser>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SteamShortcutTarget {
+    // the 64 bit id used by steam://rungameid
+    game_id: u64,
+    name: String,
+    exe: String,
+    start_dir: String,
+    launch_options: String,
+    icon: String,
+}
+
+impl PartialEq for SteamShortcutTarget {
+    fn eq(&self, other: &Self) -> bool {
+        self.game_id == other.game_id
+    }
+}
+
+impl Eq for SteamShortcutTarget {}
+
+impl Hash for SteamShortcutTarget {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.game_id.hash(state);
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    value.trim().trim_matches('"')
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+// clients before 2020 didn't store an app id, it was derived from the exe and name instead
+fn legacy_app_id(exe: &str, name: &str) -> u32 {
+    let key = format!("{}{}", exe, name);
+    crc32(key.as_bytes()) | 0x8000_0000
+}
+
+fn game_id(shortcut: &Shortcut) -> u64 {
+    let app_id = match shortcut.appid {
+        Some(id) => id as u32,
+        None => legacy_app_id(&shortcut.exe, &shortcut.app_name),
+    };
+
+    (app_id as u64) << 32 | 0x0200_0000
+}
+
+pub fn parse_shortcuts(
+    mut content: &[u8],
+) -> Result<Vec<SteamShortcutTarget>, Box<dyn std::error::Error>> {
+    let file: ShortcutsFile = Deserialize::deserialize(&mut ValveDeserializer::new(&mut content))?;
+
+    let targets = file
+        .shortcuts
+        .into_iter()
+        .filter(|shortcut| shortcut.is_hidden != Some(1))
+        .map(|shortcut| SteamShortcutTarget {
+            game_id: game_id(&shortcut),
+            exe: unquote(&shortcut.exe).to_owned(),
+            start_dir: unquote(shortcut.start_dir.as_deref().unwrap_or("")).to_owned(),
+            launch_options: shortcut.launch_options.unwrap_or_default(),
+            icon: unquote(shortcut.icon.as_deref().unwrap_or("")).to_owned(),
+            name: shortcut.app_name,
+        })
+        .collect();
+
+    Ok(targets)
+}
+
+/// A launcher target to add to steam's list of non-steam games, or to write as a .lnk
+#[derive(Debug, Clone, Default)]
+pub struct ExportShortcut {
+    pub name: String,
+    pub exe: String,
+    pub start_dir: String,
+    pub launch_options: String,
+    pub icon: String,
+    // the .lnk the target came from, if any
+    pub shortcut_path: String,
+    // the packaged app the target starts, steam has no use for it
+    pub app_user_model_id: String,
+}
+
+// the fields steam writes for a shortcut added through its own dialog
+#[derive(Serialize)]
+struct NewShortcut<'a> {
+    appid: u32,
+    #[serde(rename = "AppName")]
+    app_name: &'a str,
+    #[serde(rename = "Exe")]
+    exe: String,
+    #[serde(rename = "StartDir")]
+    start_dir: String,
+    icon: &'a str,
+    #[serde(rename = "ShortcutPath")]
+    shortcut_path: &'a str,
+    #[serde(rename = "LaunchOptions")]
+    launch_options: &'a str,
+    #[serde(rename = "IsHidden")]
+    is_hidden: bool,
+    #[serde(rename = "AllowDesktopConfig")]
+    allow_desktop_config: bool,
+    #[serde(rename = "AllowOverlay")]
+    allow_overlay: bool,
+    #[serde(rename = "OpenVR")]
+    open_vr: bool,
+    #[serde(rename = "Devkit")]
+    devkit: bool,
+    #[serde(rename = "DevkitGameID")]
+    devkit_game_id: &'a str,
+    #[serde(rename = "DevkitOverrideAppID")]
+    devkit_override_app_id: u32,
+    #[serde(rename = "LastPlayTime")]
+    last_play_time: u32,
+    tags: Vec<String>,
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value)
+}
+
+fn new_shortcut(shortcut: &ExportShortcut) -> Result<ValveValue, ValveError> {
+    let exe = quote(&shortcut.exe);
+    let entry = NewShortcut {
+        appid: legacy_app_id(&exe, &shortcut.name),
+        app_name: &shortcut.name,
+        exe,
+        start_dir: quote(&shortcut.start_dir),
+        icon: &shortcut.icon,
+        shortcut_path: &shortcut.shortcut_path,
+        launch_options: &shortcut.launch_options,
+        is_hidden: false,
+        allow_desktop_config: true,
+        allow_overlay: true,
+        open_vr: false,
+        devkit: false,
+        devkit_game_id: "",
+        devkit_override_app_id: 0,
+        last_play_time: 0,
+        tags: vec![],
+    };
+
+    let data = to_vec(&entry)?;
+    ValveValue::deserialize(&mut ValveDeserializer::new(&mut data.as_slice()))
+}
+
+fn string_field<'a>(entry: &'a ValveValue, keys: &[&str]) -> Option<&'a str> {
+    keys.iter().find_map(|&key| match entry.get(key) {
+        Some(ValveValue::String(value)) => Some(value.as_str()),
+        _ => None,
+    })
+}
+
+/// Add shortcuts to the contents of a shortcuts.vdf, leaving existing entries untouched.
+/// Shortcuts with the same name and exe as an existing entry are skipped
+pub fn add_shortcuts(
+    existing: Option<&[u8]>,
+    shortcuts: &[ExportShortcut],
+) -> Result<(Vec<u8>, usize), ValveError> {
+    let mut root = match existing {
+        Some(mut content) => ValveValue::deserialize(&mut ValveDeserializer::new(&mut content))?,
+        None => ValveValue::Object(vec![]),
+    };
+
+    if root.get("shortcuts").is_none() {
+        if let ValveValue::Object(entries) = &mut root {
+            entries.push(("shortcuts".into(), ValveValue::Object(vec![])));
+        }
+    }
+
+    let list = match root.get_mut("shortcuts") {
+        Some(ValveValue::Object(list)) => list,
+        _ => return Err(ValveError("shortcuts.vdf has no list of shortcuts".into())),
+    };
+
+    let mut added = 0;
+    for shortcut in shortcuts {
+        let exists = list.iter().any(|(_, entry)| {
+            string_field(entry, &["AppName", "appname"]) == Some(&shortcut.name)
+                && string_field(entry, &["Exe", "exe"]).map(unquote) == Some(&shortcut.exe)
+        });
+
+        if exists {
+            continue;
+        }
+
+        // entries are keyed by index
+        let index = list
+            .iter()
+            .filter_map(|(key, _)| key.parse::<usize>().ok())
+            .map(|i| i + 1)
+            .max()
+            .unwrap_or(0);
+
+        list.push((index.to_string(), new_shortcut(shortcut)?));
+        added += 1;
+    }
+
+    Ok((to_vec(&root)?, added))
+}
+
+impl SteamShortcutProvider {
+    pub fn new(config: &SteamConfig) -> SteamShortcutProvider {
+        let steam_dir = PathBuf::from(&config.path);
+        let users = selected_users(config);
+        SteamShortcutProvider { steam_dir, users }
+    }
+
+    // the config directory of the selected accounts, or of every account that has logged in
+    // on this machine if loginusers.vdf can't be read
+    fn config_dirs(&self) -> Vec<PathBuf> {
+        if !self.users.is_empty() {
+            return self
+                .users
+                .iter()
+                .map(|user| user_dir(&self.steam_dir, user).join("config"))
+                .filter(|path| path.is_dir())
+                .collect();
+        }
+
+        let userdata = self.steam_dir.join("userdata");
+
+        let users = crate::attempt!(("list steam users {:?}", userdata), {
+            userdata.read_dir()?
+        });
+
+        users
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| Some(entry.ok()?.path().join("config")))
+            .filter(|path| path.is_dir())
+            .collect()
+    }
+
+    fn shortcut_files(&self) -> Vec<PathBuf> {
+        self.config_dirs()
+            .into_iter()
+            .map(|dir| dir.join("shortcuts.vdf"))
+            .filter(|path| path.exists())
+            .collect()
+    }
+
+    /// Add shortcuts to the shortcuts.vdf of the selected accounts, backing up the original file first.
+    /// Steam only reads the file on startup, so it has to be restarted to see them
+    pub fn export(&self, shortcuts: &[ExportShortcut]) -> usize {
+        let mut added = 0;
+
+        for dir in self.config_dirs() {
+            let path = dir.join("shortcuts.vdf");
+
+            let count = crate::attempt!(("export shortcuts {:?}", path), {
+                let existing = if path.exists() {
+                    Some(std::fs::read(&path)?)
+                } else {
+                    None
+                };
+
+                let (content, count) = add_shortcuts(existing.as_deref(), shortcuts)?;
+
+                if count > 0 {
+                    if existing.is_some() {
+                        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?;
+                        let backup = dir.join(format!("shortcuts.vdf.{}.bak", now.as_secs()));
+                        std::fs::copy(&path, &backup)?;
+                    }
+
+                    std::fs::write(&path, content)?;
+                }
+
+                count
+            });
+
+            added += count.unwrap_or(0);
+        }
+
+        added
+    }
+}
+
+impl SearchProvider<SteamShortcutTarget> for SteamShortcutProvider {
+    fn index(&self) -> Vec<SteamShortcutTarget> {
+        let mut out: Vec<SteamShortcutTarget> = vec![];
+
+        for path in self.shortcut_files() {
+            let targets = crate::attempt!(("read shortcuts {:?}", path), {
+                let mut content = vec![];
+                File::open(&path)?.read_to_end(&mut content)?;
+                parse_shortcuts(&content)?
+            });
+
+            for target in targets.into_iter().flatten() {
+                if !out.contains(&target) {
+                    out.push(target);
+                }
+            }
+        }
+
+        out
+    }
+
+    fn keys(&self, entry: &SteamShortcutTarget) -> Vec<String> {
+        vec![entry.name.clone()]
+    }
+
+    fn launch(&self, entry: &SteamShortcutTarget) -> Box<dyn Fn(&LaunchOptions)> {
+        let steam_exe = self.steam_dir.join("steam.exe");
+        let game_id = entry.game_id;
+        let running = RunningMatch {
+            exe: Some(PathBuf::from(&entry.exe)),
+            ..Default::default()
+        };
+
+        Box::new(move |options| {
+            if options.focus_running() && focus_running(&running) {
+                return;
+            }
+
+            // going through steam keeps the overlay and controller configuration working
+            let mut url = format!("steam://rungameid/{}", game_id);
+            if options.arguments.len() > 0 {
+                let arguments = join_arguments(&options.arguments);
+                url = format!(
+                    "{}//{}",
+                    url,
+                    utf8_percent_encode(&arguments, NON_ALPHANUMERIC)
+                );
+            }
+
+            Command::new(&steam_exe)
+                .arg(url)
+                .spawn()
+                .expect("spawn process");
+        })
+    }
+
+    fn details(&self, entry: &SteamShortcutTarget) -> String {
+        match entry.launch_options.as_str() {
+            "" => format!("Steam shortcut: {}", entry.exe),
+            options => format!("Steam shortcut: {} {}", entry.exe, options),
+        }
+    }
+
+    fn display_icon(&self, entry: &SteamShortcutTarget) -> Option<DynamicImage> {
+        let icon = Path::new(&entry.icon);
+        let exe = Path::new(&entry.exe);
+
+        let is_image = match icon.extension().and_then(|e| e.to_str()) {
+            Some(ext) => {
+                ["png", "jpg", "jpeg", "bmp", "tga"].contains(&ext.to_lowercase().as_str())
+            }
+            None => false,
+        };
+
+        if is_image {
+            if let Some(image) =
+                crate::attempt!(("open shortcut icon {:?}", icon), image::open(icon)?)
+            {
+                return Some(image);
+            }
+        }
+
+        [icon, exe]
+            .iter()
+            .filter(|path| path.is_file())
+            .filter_map(|path| {
+                crate::attempt!(("load shortcut icon {:?}", path), {
+                    let data = extract_icons(path)?;
+                    let data = data.get(0)?;
+
+                    let r = Cursor::new(&data);
+                    let decoder = IcoDecoder::new_unchecked(r)?;
+                    DynamicImage::from_decoder(decoder)?
+                })
+            })
+            .next()
+    }
+}
+
+#[test]
+fn shortcuts() -> Result<(), Box<dyn std::error::Error>> {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/steam/shortcuts.vdf");
+    let content = std::fs::read(fixture)?;
+
+    let targets = parse_shortcuts(&content)?;
+    assert_eq!(targets.len(), 2);
+
+    let dolphin = &targets[0];
+    assert_eq!(dolphin.name, "Dolphin");
+    assert_eq!(dolphin.game_id, 12_870_183_387_297_480_704);
+    assert_eq!(dolphin.exe, r"C:\Emulators\Dolphin\Dolphin.exe");
+    assert_eq!(dolphin.start_dir, r"C:\Emulators\Dolphin\");
+    assert_eq!(dolphin.launch_options, "--batch");
+    assert_eq!(dolphin.icon, r"C:\Emulators\Dolphin\icon.png");
+
+    let old = &targets[1];
+    assert_eq!(old.name, "Old Game");
+    assert_eq!(old.exe, r"D:\itch\old-game\game.exe");
+    assert_eq!(
+        old.game_id >> 32,
+        0x8000_0000 | crc32(br#""D:\itch\old-game\game.exe"Old Game"#) as u64
+    );
+    assert_eq!(old.game_id & 0xFFFF_FFFF, 0x0200_0000);
+
+    Ok(())
+}
+
+#[test]
+fn export_shortcuts() -> Result<(), Box<dyn std::error::Error>> {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/steam/shortcuts.vdf");
+    let content = std::fs::read(fixture)?;
+
+    let retroarch = ExportShortcut {
+        name: "RetroArch".into(),
+        exe: r"C:\RetroArch\retroarch.exe".into(),
+        start_dir: r"C:\RetroArch\".into(),
+        launch_options: "--menu".into(),
+        ..Default::default()
+    };
+
+    let dolphin = ExportShortcut {
+        name: "Dolphin".into(),
+        exe: r"C:\Emulators\Dolphin\Dolphin.exe".into(),
+        ..Default::default()
+    };
+
+    let (output, added) = add_shortcuts(Some(&content), &[retroarch.clone(), dolphin])?;
+    assert_eq!(added, 1);
+
+    // existing entries are written back byte for byte
+    let mut original = ValveValue::deserialize(&mut ValveDeserializer::new(&mut &content[..]))?;
+    let mut updated = ValveValue::deserialize(&mut ValveDeserializer::new(&mut &output[..]))?;
+    match (original.get_mut("shortcuts"), updated.get_mut("shortcuts")) {
+        (Some(ValveValue::Object(a)), Some(ValveValue::Object(b))) => {
+            let (key, _) = b.pop().unwrap();
+            assert_eq!(key, "3");
+            assert_eq!(a, b);
+        }
+        other => panic!("no list of shortcuts: {:?}", other),
+    }
+    assert_eq!(to_vec(&original)?, content);
+
+    let targets = parse_shortcuts(&output)?;
+    let target = targets.iter().find(|t| t.name == "RetroArch").unwrap();
+    assert_eq!(target.exe, r"C:\RetroArch\retroarch.exe");
+    assert_eq!(target.start_dir, r"C:\RetroArch\");
+    assert_eq!(target.launch_options, "--menu");
+
+    let (fresh, added) = add_shortcuts(None, &[retroarch])?;
+    assert_eq!(added, 1);
+    assert_eq!(parse_shortcuts(&fresh)?.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn shortcut_order() -> Result<(), Box<dyn std::error::Error>> {
+    let names: Vec<_> = (0..12).map(|i| format!("Game {}", i)).collect();
+    let shortcuts: Vec<_> = names
+        .iter()
+        .map(|name| ExportShortcut {
+            name: name.clone(),
+            exe: format!(r"C:\Games\{}.exe", name),
+            ..Default::default()
+        })
+        .collect();
+
+    // "10" and "11" come after "9", not after "1"
+    let (content, _) = add_shortcuts(None, &shortcuts)?;
+    let targets = parse_shortcuts(&content)?;
+    let parsed: Vec<_> = targets.into_iter().map(|t| t.name).collect();
+    assert_eq!(parsed, names);
+
+    Ok(())
+}
@@ -0,0 +1,359 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use super::vdf_text::{self, AnyValue, TextError};
+use crate::common::read_user_dword;
+
+// steam ids of individual accounts are the account id plus this
+const STEAM_ID_BASE: u64 = 76_561_197_960_265_728;
+
+#[derive(Debug, Clone)]
+pub struct SteamUser {
+    pub steam_id: u64,
+    pub account_name: String,
+    pub persona_name: String,
+    pub most_recent: bool,
+    pub timestamp: u64,
+}
+
+impl SteamUser {
+    /// The id used for the user's directory in userdata
+    pub fn account_id(&self) -> u64 {
+        self.steam_id - STEAM_ID_BASE
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct UserAppData {
+    // unix time
+    #[serde(rename = "LastPlayed", default)]
+    pub last_played: u64,
+    // minutes
+    #[serde(rename = "Playtime", default)]
+    pub playtime: u32,
+    #[serde(rename = "LaunchOptions", default)]
+    pub launch_options: String,
+}
+
+/// Parse config/loginusers.vdf
+pub fn parse_login_users(content: &str) -> Result<Vec<SteamUser>, TextError> {
+    let root = vdf_text::parse(content)?;
+    let users = root.get("users").map(AnyValue::entries).unwrap_or(&[]);
+
+    let users = users
+        .iter()
+        .filter_map(|(id, user)| {
+            let number = |key| user.get_str(key).and_then(|v| v.parse().ok());
+
+            Some(SteamUser {
+                steam_id: id.parse().ok().filter(|&id| id > STEAM_ID_BASE)?,
+                account_name: user.get_str("AccountName").unwrap_or("").to_owned(),
+                persona_name: user.get_str("PersonaName").unwrap_or("").to_owned(),
+                most_recent: number("MostRecent") == Some(1),
+                timestamp: number("Timestamp").unwrap_or(0),
+            })
+        })
+        .collect();
+
+    Ok(users)
+}
+
+/// The user steam logs in as, falls back to the latest login if none is marked
+pub fn most_recent_user(users: &[SteamUser]) -> Option<&SteamUser> {
+    users
+        .iter()
+        .find(|u| u.most_recent)
+        .or_else(|| users.iter().max_by_key(|u| u.timestamp))
+}
+
+/// Parse the per app data in userdata/<account id>/config/localconfig.vdf
+pub fn parse_local_config(content: &str) -> Result<HashMap<u32, UserAppData>, TextError> {
+    let root = vdf_text::parse(content)?;
+
+    let apps = ["UserLocalConfigStore", "Software", "Valve", "Steam", "apps"]
+        .iter()
+        .try_fold(&root, |value, key| value.get(key));
+
+    let mut out = HashMap::new();
+    for (id, app) in apps.map(AnyValue::entries).unwrap_or(&[]) {
+        let id = match id.parse() {
+            Ok(id) => id,
+            Err(_) => continue,
+        };
+
+        let data = crate::attempt!(("read local config for {}", id), {
+            serde::Deserialize::deserialize(app.clone())?
+        });
+
+        if let Some(data) = data {
+            out.insert(id, data);
+        }
+    }
+
+    Ok(out)
+}
+
+/// Parse the app ids in userdata/<account id>/7/remote/sharedconfig.vdf, which is synced
+/// between machines and lists apps that have tags or cloud data
+pub fn parse_shared_config(content: &str) -> Result<HashSet<u32>, TextError> {
+    let root = vdf_text::parse(content)?;
+
+    let ids = shared_config_apps(&root)
+        .map(AnyValue::entries)
+        .unwrap_or(&[]);
+    Ok(ids.iter().filter_map(|(id, _)| id.parse().ok()).collect())
+}
+
+/// The apps object of a parsed sharedconfig.vdf
+pub fn shared_config_apps(root: &AnyValue) -> Option<&AnyValue> {
+    // older clients used the same root name as localconfig.vdf
+    ["UserRoamingConfigStore", "UserLocalConfigStore"]
+        .iter()
+        .filter_map(|root_key| {
+            ["Software", "Valve", "Steam", "apps"]
+                .iter()
+                .try_fold(root.get(root_key)?, |value, key| value.get(key))
+        })
+        .next()
+}
+
+/// Which accounts to show the games and data of
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccountSelection {
+    // the account steam is logged in to, or the last one it was
+    Active,
+    // an account or persona name
+    Named(String),
+    // every account, with each game tagged with the accounts that know it (see load_known_apps)
+    All,
+}
+
+impl AccountSelection {
+    pub fn from_config(account: Option<&str>) -> AccountSelection {
+        match account {
+            None | Some("") => AccountSelection::Active,
+            Some(name) if name.eq_ignore_ascii_case("all") => AccountSelection::All,
+            Some(name) => AccountSelection::Named(name.to_owned()),
+        }
+    }
+}
+
+/// The users to read data for. `active` is the account id of the user steam is running as
+pub fn select_users(
+    users: &[SteamUser],
+    selection: &AccountSelection,
+    active: Option<u64>,
+) -> Vec<SteamUser> {
+    let active = || {
+        let running = users.iter().find(|u| Some(u.account_id()) == active);
+        running.or_else(|| most_recent_user(users)).cloned()
+    };
+
+    match selection {
+        AccountSelection::All => users.to_vec(),
+        AccountSelection::Active => active().into_iter().collect(),
+        AccountSelection::Named(name) => {
+            let named: Vec<_> = users
+                .iter()
+                .filter(|u| {
+                    u.account_name.eq_ignore_ascii_case(name)
+                        || u.persona_name.eq_ignore_ascii_case(name)
+                })
+                .cloned()
+                .collect();
+
+            if named.is_empty() {
+                crate::log!("no steam account named {:?}, using the active one", name);
+                return active().into_iter().collect();
+            }
+
+            named
+        }
+    }
+}
+
+pub fn load_users(steam_dir: &Path) -> Result<Vec<SteamUser>, Box<dyn std::error::Error>> {
+    let content = std::fs::read_to_string(steam_dir.join("config/loginusers.vdf"))?;
+    Ok(parse_login_users(&content)?)
+}
+
+/// The account id steam is currently logged in as
+pub fn active_account_id() -> Option<u64> {
+    let id = read_user_dword(r"Software\Valve\Steam\ActiveProcess", "ActiveUser")?;
+    Some(id as u64).filter(|&id| id != 0)
+}
+
+pub fn user_dir(steam_dir: &Path, user: &SteamUser) -> PathBuf {
+    steam_dir
+        .join("userdata")
+        .join(user.account_id().to_string())
+}
+
+/// Read the app data of a user from their localconfig.vdf
+pub fn load_user_apps(
+    steam_dir: &Path,
+    user: &SteamUser,
+) -> Result<HashMap<u32, UserAppData>, Box<dyn std::error::Error>> {
+    let path = user_dir(steam_dir, user).join("config/localconfig.vdf");
+    let content = std::fs::read_to_string(path)?;
+    Ok(parse_local_config(&content)?)
+}
+
+/// The apps steam has stored settings for, locally or in the cloud. This only approximates what
+/// the user owns: the real list of licenses is in the encrypted licensecache, so games that were
+/// never started are missing, and ones played through family sharing or a free weekend are kept
+pub fn load_known_apps(
+    steam_dir: &Path,
+    user: &SteamUser,
+    user_apps: &HashMap<u32, UserAppData>,
+) -> HashSet<u32> {
+    let path = user_dir(steam_dir, user).join("7/remote/sharedconfig.vdf");
+    let shared = crate::attempt!(("read steam shared config {:?}", path), {
+        parse_shared_config(&std::fs::read_to_string(&path)?)?
+    });
+
+    let mut known = shared.unwrap_or_default();
+    known.extend(user_apps.keys());
+    known
+}
+
+/// Combine the app data of another account, keeping the latest launch and the total playtime
+pub fn merge_user_apps(into: &mut HashMap<u32, UserAppData>, other: HashMap<u32, UserAppData>) {
+    for (app_id, data) in other {
+        let existing = into.entry(app_id).or_default();
+        if data.last_played > existing.last_played {
+            existing.last_played = data.last_played;
+            existing.launch_options = data.launch_options;
+        }
+
+        existing.playtime += data.playtime;
+    }
+}
+
+pub fn describe_playtime(minutes: u32) -> String {
+    match minutes {
+        0..=59 => format!("{} min", minutes),
+        _ => format!("{:.1} h", minutes as f64 / 60.0),
+    }
+}
+
+pub fn describe_last_played(last_played: u64, now: u64) -> String {
+    let days = now.saturating_sub(last_played) / (24 * 60 * 60);
+    match days {
+        0 => "today".into(),
+        1 => "yesterday".into(),
+        2..=13 => format!("{} days ago", days),
+        14..=59 => format!("{} weeks ago", days / 7),
+        60..=729 => format!("{} months ago", days / 30),
+        _ => format!("{} years ago", days / 365),
+    }
+}
+
+#[test]
+fn login_users() -> Result<(), Box<dyn std::error::Error>> {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/steam/loginusers.vdf");
+    let users = parse_login_users(&std::fs::read_to_string(fixture)?)?;
+
+    assert_eq!(users.len(), 2);
+    let user = most_recent_user(&users).unwrap();
+    assert_eq!(user.account_name, "player");
+    assert_eq!(user.account_id(), 39_734_273);
+
+    // without a MostRecent flag the latest login wins
+    let users: Vec<_> = users
+        .into_iter()
+        .map(|u| SteamUser {
+            most_recent: false,
+            ..u
+        })
+        .collect();
+    assert_eq!(most_recent_user(&users).unwrap().account_name, "gaben");
+
+    Ok(())
+}
+
+#[test]
+fn select_accounts() -> Result<(), Box<dyn std::error::Error>> {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/steam/loginusers.vdf");
+    let users = parse_login_users(&std::fs::read_to_string(fixture)?)?;
+
+    let names = |selection, active| -> Vec<String> {
+        let selected = select_users(&users, &selection, active);
+        selected.into_iter().map(|u| u.account_name).collect()
+    };
+
+    assert_eq!(names(AccountSelection::Active, None), vec!["player"]);
+    assert_eq!(names(AccountSelection::Active, Some(22202)), vec!["gaben"]);
+    assert_eq!(
+        names(AccountSelection::from_config(Some("gabe")), None),
+        vec!["gaben"]
+    );
+    assert_eq!(
+        names(AccountSelection::from_config(Some("nobody")), None),
+        vec!["player"]
+    );
+    assert_eq!(
+        names(AccountSelection::from_config(Some("All")), None),
+        vec!["gaben", "player"]
+    );
+
+    let mut apps = HashMap::new();
+    let app = |last_played, playtime, launch_options: &str| UserAppData {
+        last_played,
+        playtime,
+        launch_options: launch_options.to_owned(),
+    };
+
+    merge_user_apps(
+        &mut apps,
+        vec![(620, app(100, 30, "-novid"))].into_iter().collect(),
+    );
+    merge_user_apps(
+        &mut apps,
+        vec![(620, app(200, 15, ""))].into_iter().collect(),
+    );
+    assert_eq!(apps[&620].last_played, 200);
+    assert_eq!(apps[&620].playtime, 45);
+    assert_eq!(apps[&620].launch_options, "");
+
+    Ok(())
+}
+
+#[test]
+fn shared_config() -> Result<(), Box<dyn std::error::Error>> {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/steam/sharedconfig.vdf");
+    let apps = parse_shared_config(&std::fs::read_to_string(fixture)?)?;
+
+    let expected: HashSet<u32> = [220, 400, 620].iter().cloned().collect();
+    assert_eq!(apps, expected);
+
+    Ok(())
+}
+
+#[test]
+fn local_config() -> Result<(), Box<dyn std::error::Error>> {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/steam/localconfig.vdf");
+    let apps = parse_local_config(&std::fs::read_to_string(fixture)?)?;
+
+    assert_eq!(apps.len(), 3);
+    assert_eq!(apps[&620].last_played, 1_601_500_000);
+    assert_eq!(apps[&620].playtime, 1234);
+    assert_eq!(apps[&620].launch_options, "-novid -console");
+    assert_eq!(apps[&440].playtime, 45);
+    assert_eq!(apps[&228980].last_played, 0);
+
+    assert_eq!(describe_playtime(45), "45 min");
+    assert_eq!(describe_playtime(1234), "20.6 h");
+    assert_eq!(
+        describe_last_played(1_601_500_000, 1_601_500_000 + 3 * 86400),
+        "3 days ago"
+    );
+    assert_eq!(
+        describe_last_played(1_580_000_000, 1_601_500_000),
+        "8 months ago"
+    );
+
+    Ok(())
+}
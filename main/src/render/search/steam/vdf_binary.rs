@@ -1,11 +1,14 @@
 use std::{fmt::Debug, fmt::Display};
 
-use serde::{de::MapAccess, de::SeqAccess, de::Visitor, forward_to_deserialize_any, Deserializer};
+use serde::{
+    de::MapAccess, de::SeqAccess, de::Visitor, forward_to_deserialize_any, ser, Deserialize,
+    Deserializer, Serialize, Serializer,
+};
 
 use flat::prelude::*;
 
 #[derive(Debug)]
-pub struct ValveError(String);
+pub struct ValveError(pub String);
 
 impl Display for ValveError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -24,6 +27,15 @@ impl serde::de::Error for ValveError {
     }
 }
 
+impl serde::ser::Error for ValveError {
+    fn custom<T>(msg: T) -> Self
+    where
+        T: std::fmt::Display,
+    {
+        ValveError(msg.to_string())
+    }
+}
+
 pub struct ValveDeserializer<'a, 'de> {
     src: ValveReader<'a, 'de>,
     key: Option<&'de str>,
@@ -37,6 +49,16 @@ impl<'a, 'de> ValveDeserializer<'a, 'de> {
         let value = None;
         ValveDeserializer { src, key, value }
     }
+
+    pub fn with_string_table(
+        src: &'a mut &'de [u8],
+        strings: &'a [&'de str],
+    ) -> ValveDeserializer<'a, 'de> {
+        let src = ValveReader::with_string_table(src, strings);
+        let key = None;
+        let value = None;
+        ValveDeserializer { src, key, value }
+    }
 }
 
 impl<'de> SeqAccess<'de> for ValveDeserializer<'_, 'de> {
@@ -46,7 +68,7 @@ impl<'de> SeqAccess<'de> for ValveDeserializer<'_, 'de> {
     where
         T: serde::de::DeserializeSeed<'de>,
     {
-        let x = self.src.next();
+        let x = self.src.next().transpose()?;
         match x {
             None => Ok(None),
             Some(None) => Ok(None),
@@ -65,7 +87,7 @@ impl<'de> MapAccess<'de> for ValveDeserializer<'_, 'de> {
     where
         K: serde::de::DeserializeSeed<'de>,
     {
-        let x = self.src.next();
+        let x = self.src.next().transpose()?;
         match x {
             None => Ok(None),
             Some(None) => Ok(None),
@@ -123,7 +145,8 @@ impl<'de> Deserializer<'de> for &mut ValveDeserializer<'_, 'de> {
                 ValveToken::F32(v) => visitor.visit_f32(v),
             }
         } else {
-            panic!()
+            // the file itself is an object without a header
+            visitor.visit_map(self)
         }
     }
 
@@ -154,37 +177,78 @@ pub enum ValveToken<'a> {
 pub struct ValveReader<'a, 'b> {
     src: &'a mut &'b [u8],
     depth: usize,
+    // newer appinfo.vdf files store keys as indices into a table at the end of the file
+    strings: Option<&'a [&'b str]>,
 }
 
 impl<'a, 'b> ValveReader<'a, 'b> {
     pub fn new(src: &'a mut &'b [u8]) -> ValveReader<'a, 'b> {
         let depth = 0;
-        ValveReader { src, depth }
+        let strings = None;
+        ValveReader {
+            src,
+            depth,
+            strings,
+        }
+    }
+
+    pub fn with_string_table(src: &'a mut &'b [u8], strings: &'a [&'b str]) -> ValveReader<'a, 'b> {
+        let depth = 0;
+        let strings = Some(strings);
+        ValveReader {
+            src,
+            depth,
+            strings,
+        }
+    }
+
+    fn load<T: Flat>(&mut self) -> Result<T, ValveError> {
+        if self.src.len() < T::SIZE {
+            return Err(ValveError("unexpected end of key values".into()));
+        }
+
+        Ok(self.src.load())
     }
 
-    fn read_string(&mut self) -> &'b str {
-        let strlen = self.src.iter().position(|&c| c == 0).unwrap();
+    fn read_string(&mut self) -> Result<&'b str, ValveError> {
+        let strlen = match self.src.iter().position(|&c| c == 0) {
+            Some(strlen) => strlen,
+            None => return Err(ValveError("unterminated valve string".into())),
+        };
+
         let bytes = self.src.load_slice(strlen);
         *self.src = &self.src[1..];
-        std::str::from_utf8(bytes).unwrap()
+        std::str::from_utf8(bytes).map_err(|e| ValveError(format!("invalid valve string: {}", e)))
     }
-}
 
-impl<'a, 'b> Iterator for ValveReader<'a, 'b> {
-    type Item = Option<(&'b str, ValveToken<'b>)>;
+    fn read_key(&mut self) -> Result<&'b str, ValveError> {
+        match self.strings {
+            None => self.read_string(),
+            Some(strings) => {
+                let index = self.load::<u32le>()?.get() as usize;
+                match strings.get(index) {
+                    Some(key) => Ok(key),
+                    None => Err(ValveError(format!(
+                        "invalid valve string table index {}",
+                        index
+                    ))),
+                }
+            }
+        }
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let ty: u8 = self.src.load();
+    fn read_token(&mut self) -> Result<Option<Option<(&'b str, ValveToken<'b>)>>, ValveError> {
+        let ty: u8 = self.load()?;
         if ty == 0x08 || ty == 0x0b {
             if self.depth == 0 {
-                return None;
+                return Ok(None);
             } else {
                 self.depth -= 1;
-                return Some(None);
+                return Ok(Some(None));
             }
         }
 
-        let name = self.read_string();
+        let name = self.read_key()?;
         let value = match ty {
             // child object
             0x00 => {
@@ -192,25 +256,742 @@ impl<'a, 'b> Iterator for ValveReader<'a, 'b> {
                 ValveToken::Object
             }
             // string
-            0x01 => ValveToken::String(self.read_string()),
+            0x01 => ValveToken::String(self.read_string()?),
             // int 32
-            0x02 => ValveToken::I32(self.src.load()),
+            0x02 => ValveToken::I32(self.load::<i32le>()?.get()),
             // float 32
-            0x03 => ValveToken::F32(self.src.load()),
-            // pointer?
-            0x04 => panic!("unsupported valve type {:x}", ty),
-            // wide string
-            0x05 => panic!("unsupported valve type {:x}", ty),
-            // color?
-            0x06 => panic!("unsupported valve type {:x}", ty),
+            0x03 => ValveToken::F32(self.load::<f32le>()?.get()),
             // uint 64
-            0x07 => ValveToken::U64(self.src.load()),
-            // probably binary?
-            0x09 => panic!("unsupported valve type {:x}", ty),
+            0x07 => ValveToken::U64(self.load::<u64le>()?.get()),
             // int 64
-            0xa => ValveToken::I64(self.src.load()),
-            _ => panic!("unsupported valve type {:x}", ty),
+            0xa => ValveToken::I64(self.load::<i64le>()?.get()),
+            // 0x04 pointer, 0x05 wide string, 0x06 color and 0x09 binary aren't used by steam
+            _ => return Err(ValveError(format!("unsupported valve type {:x}", ty))),
+        };
+
+        Ok(Some(Some((name, value))))
+    }
+}
+
+impl<'a, 'b> Iterator for ValveReader<'a, 'b> {
+    // None closes an object
+    type Item = Result<Option<(&'b str, ValveToken<'b>)>, ValveError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_token().transpose()
+    }
+}
+
+/// An owned key values tree, keeps the exact token types and key order of a file
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValveValue {
+    Object(Vec<(String, ValveValue)>),
+    String(String),
+    I32(i32),
+    I64(i64),
+    U64(u64),
+    F32(f32),
+}
+
+impl ValveValue {
+    pub fn get(&self, key: &str) -> Option<&ValveValue> {
+        match self {
+            ValveValue::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut ValveValue> {
+        match self {
+            ValveValue::Object(entries) => {
+                entries.iter_mut().find(|(k, _)| k == key).map(|(_, v)| v)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ValveValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ValueVisitor;
+
+        impl<'de> Visitor<'de> for ValueVisitor {
+            type Value = ValveValue;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "a valve key value")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<ValveValue, E> {
+                Ok(ValveValue::String(v.to_owned()))
+            }
+
+            fn visit_i32<E>(self, v: i32) -> Result<ValveValue, E> {
+                Ok(ValveValue::I32(v))
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<ValveValue, E> {
+                Ok(ValveValue::I64(v))
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<ValveValue, E> {
+                Ok(ValveValue::U64(v))
+            }
+
+            fn visit_f32<E>(self, v: f32) -> Result<ValveValue, E> {
+                Ok(ValveValue::F32(v))
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<ValveValue, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut entries = vec![];
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+
+                Ok(ValveValue::Object(entries))
+            }
+        }
+
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+impl Serialize for ValveValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use serde::ser::SerializeMap;
+
+        match self {
+            ValveValue::Object(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+            ValveValue::String(v) => serializer.serialize_str(v),
+            ValveValue::I32(v) => serializer.serialize_i32(*v),
+            ValveValue::I64(v) => serializer.serialize_i64(*v),
+            ValveValue::U64(v) => serializer.serialize_u64(*v),
+            ValveValue::F32(v) => serializer.serialize_f32(*v),
+        }
+    }
+}
+
+/// Serialize a value as a binary key values file
+pub fn to_vec<T: Serialize>(value: &T) -> Result<Vec<u8>, ValveError> {
+    let mut out = vec![];
+    value.serialize(ValveSerializer::new(&mut out))?;
+    Ok(out)
+}
+
+// the inverse of `ValveReader`, except that u32 and bool are written as int 32
+// and f64 is narrowed to float 32, since the format has no tokens for them
+pub struct ValveSerializer<'a> {
+    out: &'a mut Vec<u8>,
+    // the key for the value being written, None for the top level object
+    key: Option<String>,
+}
+
+impl<'a> ValveSerializer<'a> {
+    pub fn new(out: &'a mut Vec<u8>) -> ValveSerializer<'a> {
+        let key = None;
+        ValveSerializer { out, key }
+    }
+
+    fn header(&mut self, ty: u8) -> Result<(), ValveError> {
+        match self.key.take() {
+            None => Err(ValveError("top level value must be an object".into())),
+            Some(key) => {
+                self.out.push(ty);
+                write_string(self.out, &key)
+            }
+        }
+    }
+
+    fn begin_object(mut self) -> Result<ValveCompound<'a>, ValveError> {
+        if self.key.is_some() {
+            self.header(0x00)?;
+        }
+
+        let out = self.out;
+        let next_index = 0;
+        let key = None;
+        Ok(ValveCompound {
+            out,
+            next_index,
+            key,
+        })
+    }
+}
+
+fn write_string(out: &mut Vec<u8>, value: &str) -> Result<(), ValveError> {
+    if value.contains('\0') {
+        return Err(ValveError(format!(
+            "string contains a nul byte: {:?}",
+            value
+        )));
+    }
+
+    out.extend(value.as_bytes());
+    out.push(0);
+    Ok(())
+}
+
+fn unsupported<T>(what: &str) -> Result<T, ValveError> {
+    Err(ValveError(format!(
+        "cannot serialize {} as a valve key value",
+        what
+    )))
+}
+
+impl<'a> Serializer for ValveSerializer<'a> {
+    type Ok = ();
+    type Error = ValveError;
+
+    type SerializeSeq = ValveCompound<'a>;
+    type SerializeTuple = ValveCompound<'a>;
+    type SerializeTupleStruct = ValveCompound<'a>;
+    type SerializeTupleVariant = ser::Impossible<(), ValveError>;
+    type SerializeMap = ValveCompound<'a>;
+    type SerializeStruct = ValveCompound<'a>;
+    type SerializeStructVariant = ser::Impossible<(), ValveError>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), ValveError> {
+        self.serialize_i32(v as i32)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), ValveError> {
+        self.serialize_i32(v as i32)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), ValveError> {
+        self.serialize_i32(v as i32)
+    }
+
+    fn serialize_i32(mut self, v: i32) -> Result<(), ValveError> {
+        self.header(0x02)?;
+        self.out.extend(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_i64(mut self, v: i64) -> Result<(), ValveError> {
+        self.header(0x0a)?;
+        self.out.extend(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), ValveError> {
+        self.serialize_i32(v as i32)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), ValveError> {
+        self.serialize_i32(v as i32)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), ValveError> {
+        self.serialize_i32(v as i32)
+    }
+
+    fn serialize_u64(mut self, v: u64) -> Result<(), ValveError> {
+        self.header(0x07)?;
+        self.out.extend(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_f32(mut self, v: f32) -> Result<(), ValveError> {
+        self.header(0x03)?;
+        self.out.extend(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), ValveError> {
+        self.serialize_f32(v as f32)
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), ValveError> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(mut self, v: &str) -> Result<(), ValveError> {
+        self.header(0x01)?;
+        write_string(self.out, v)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), ValveError> {
+        unsupported("bytes")
+    }
+
+    // missing values are left out of the object
+    fn serialize_none(self) -> Result<(), ValveError> {
+        Ok(())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), ValveError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), ValveError> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), ValveError> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<(), ValveError> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), ValveError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), ValveError> {
+        unsupported("an enum variant")
+    }
+
+    // sequences are objects keyed by index, like "0", "1", ...
+    fn serialize_seq(self, _len: Option<usize>) -> Result<ValveCompound<'a>, ValveError> {
+        self.begin_object()
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<ValveCompound<'a>, ValveError> {
+        self.begin_object()
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<ValveCompound<'a>, ValveError> {
+        self.begin_object()
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, ValveError> {
+        unsupported("an enum variant")
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<ValveCompound<'a>, ValveError> {
+        self.begin_object()
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<ValveCompound<'a>, ValveError> {
+        self.begin_object()
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, ValveError> {
+        unsupported("an enum variant")
+    }
+}
+
+pub struct ValveCompound<'a> {
+    out: &'a mut Vec<u8>,
+    next_index: usize,
+    // the key of a map entry, between serialize_key and serialize_value
+    key: Option<String>,
+}
+
+impl ValveCompound<'_> {
+    fn field<T: ?Sized + Serialize>(&mut self, key: String, value: &T) -> Result<(), ValveError> {
+        let out = &mut *self.out;
+        let key = Some(key);
+        value.serialize(ValveSerializer { out, key })
+    }
+
+    fn element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ValveError> {
+        let key = self.next_index.to_string();
+        self.next_index += 1;
+        self.field(key, value)
+    }
+
+    fn finish(self) -> Result<(), ValveError> {
+        self.out.push(0x08);
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for ValveCompound<'_> {
+    type Ok = ();
+    type Error = ValveError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ValveError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), ValveError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for ValveCompound<'_> {
+    type Ok = ();
+    type Error = ValveError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ValveError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), ValveError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for ValveCompound<'_> {
+    type Ok = ();
+    type Error = ValveError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ValveError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), ValveError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeMap for ValveCompound<'_> {
+    type Ok = ();
+    type Error = ValveError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), ValveError> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ValveError> {
+        match self.key.take() {
+            Some(key) => self.field(key, value),
+            None => Err(ValveError("map value without a key".into())),
+        }
+    }
+
+    fn end(self) -> Result<(), ValveError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for ValveCompound<'_> {
+    type Ok = ();
+    type Error = ValveError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ValveError> {
+        self.field(key.to_owned(), value)
+    }
+
+    fn end(self) -> Result<(), ValveError> {
+        self.finish()
+    }
+}
+
+// map keys can be strings or integers
+struct KeySerializer;
+
+impl Serializer for KeySerializer {
+    type Ok = String;
+    type Error = ValveError;
+
+    type SerializeSeq = ser::Impossible<String, ValveError>;
+    type SerializeTuple = ser::Impossible<String, ValveError>;
+    type SerializeTupleStruct = ser::Impossible<String, ValveError>;
+    type SerializeTupleVariant = ser::Impossible<String, ValveError>;
+    type SerializeMap = ser::Impossible<String, ValveError>;
+    type SerializeStruct = ser::Impossible<String, ValveError>;
+    type SerializeStructVariant = ser::Impossible<String, ValveError>;
+
+    fn serialize_bool(self, _v: bool) -> Result<String, ValveError> {
+        unsupported("a bool key")
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<String, ValveError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<String, ValveError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<String, ValveError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<String, ValveError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<String, ValveError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<String, ValveError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<String, ValveError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<String, ValveError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<String, ValveError> {
+        unsupported("a float key")
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<String, ValveError> {
+        unsupported("a float key")
+    }
+
+    fn serialize_char(self, v: char) -> Result<String, ValveError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<String, ValveError> {
+        Ok(v.to_owned())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String, ValveError> {
+        unsupported("a bytes key")
+    }
+
+    fn serialize_none(self) -> Result<String, ValveError> {
+        unsupported("an empty key")
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<String, ValveError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<String, ValveError> {
+        unsupported("an empty key")
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, ValveError> {
+        unsupported("an empty key")
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<String, ValveError> {
+        Ok(variant.to_owned())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, ValveError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, ValveError> {
+        unsupported("an enum key")
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, ValveError> {
+        unsupported("a sequence key")
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, ValveError> {
+        unsupported("a tuple key")
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, ValveError> {
+        unsupported("a tuple key")
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, ValveError> {
+        unsupported("an enum key")
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, ValveError> {
+        unsupported("a map key")
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, ValveError> {
+        unsupported("a struct key")
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, ValveError> {
+        unsupported("an enum key")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(mut data: &[u8]) -> ValveValue {
+        ValveValue::deserialize(&mut ValveDeserializer::new(&mut data)).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut data = vec![];
+        data.push(0x00);
+        data.extend(b"root\0");
+        data.push(0x01);
+        data.extend(b"name\0Half-Life\0");
+        data.push(0x02);
+        data.extend(b"appid\0");
+        data.extend(&(-70i32).to_le_bytes());
+        data.push(0x03);
+        data.extend(b"scale\0");
+        data.extend(&1.5f32.to_le_bytes());
+        data.push(0x07);
+        data.extend(b"gameid\0");
+        data.extend(&u64::MAX.to_le_bytes());
+        data.push(0x0a);
+        data.extend(b"delta\0");
+        data.extend(&(-5i64).to_le_bytes());
+        data.push(0x00);
+        data.extend(b"empty\0");
+        data.push(0x08);
+        data.extend(&[0x08, 0x08]);
+
+        let value = parse(&data);
+        assert_eq!(
+            value.get("root").unwrap().get("scale"),
+            Some(&ValveValue::F32(1.5))
+        );
+        assert_eq!(to_vec(&value).unwrap(), data);
+    }
+
+    #[test]
+    fn corrupt_data() {
+        let corrupt = |mut data: &[u8]| {
+            ValveValue::deserialize(&mut ValveDeserializer::new(&mut data)).unwrap_err()
+        };
+
+        // no nul after the string, then invalid utf-8
+        corrupt(b"\x01name\0Half-Life");
+        corrupt(b"\x01name\0\xff\0\x08");
+        // cut off in the middle of an int, and before the end of the object
+        corrupt(b"\x02appid\0\x01\x02");
+        corrupt(b"\x00root\0\x01name\0a\0");
+        corrupt(b"\x05wide\0\0\0\x08");
+
+        let strings = ["appid"];
+        let mut data = &[0x02, 1, 0, 0, 0, 0, 0, 0, 0, 0x08][..];
+        let mut de = ValveDeserializer::with_string_table(&mut data, &strings);
+        let error = ValveValue::deserialize(&mut de).unwrap_err();
+        assert!(error.0.contains("string table index 1"), "{}", error);
+    }
+
+    #[test]
+    fn serialize_structs() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Shortcut {
+            appid: u32,
+            #[serde(rename = "AppName")]
+            app_name: String,
+            icon: Option<String>,
+            tags: Vec<String>,
+            hidden: bool,
+        }
+
+        let shortcut = Shortcut {
+            appid: 0x8000_0001,
+            app_name: "Dolphin".into(),
+            icon: None,
+            tags: vec!["Emulators".into(), "Favorites".into()],
+            hidden: false,
         };
-        Some(Some((name, value)))
+
+        let data = to_vec(&shortcut).unwrap();
+        let value = parse(&data);
+        let expected = ValveValue::Object(vec![
+            ("appid".into(), ValveValue::I32(0x8000_0001u32 as i32)),
+            ("AppName".into(), ValveValue::String("Dolphin".into())),
+            (
+                "tags".into(),
+                ValveValue::Object(vec![
+                    ("0".into(), ValveValue::String("Emulators".into())),
+                    ("1".into(), ValveValue::String("Favorites".into())),
+                ]),
+            ),
+            ("hidden".into(), ValveValue::I32(0)),
+        ]);
+
+        assert_eq!(value, expected);
+        assert!(to_vec(&"top level string").is_err());
+        assert!(to_vec(&ValveValue::Object(vec![(
+            "a".into(),
+            ValveValue::String("\0".into())
+        )]))
+        .is_err());
     }
 }
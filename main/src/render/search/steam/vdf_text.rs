@@ -1,54 +1,1302 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
 
-use nom::{
-    branch::alt, bytes::complete::escaped, bytes::complete::take_while, character::complete::char,
-    character::complete::none_of, character::complete::one_of, combinator::map,
-    multi::separated_list, sequence::delimited, sequence::separated_pair, IResult,
+use serde::{
+    de::{self, DeserializeOwned, IntoDeserializer, Visitor},
+    forward_to_deserialize_any, ser, Serialize,
 };
 
-#[derive(Debug, Clone)]
+/// A text key values tree, keeps duplicate keys and the order of the file
+#[derive(Debug, Clone, PartialEq)]
 pub enum AnyValue {
     String(String),
-    Map(HashMap<String, AnyValue>),
+    Map(Vec<(String, AnyValue)>),
+}
+
+impl AnyValue {
+    /// The first value with a key, compared case insensitively like steam does
+    pub fn get(&self, key: &str) -> Option<&AnyValue> {
+        self.entries()
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut AnyValue> {
+        match self {
+            AnyValue::String(_) => None,
+            AnyValue::Map(entries) => entries
+                .iter_mut()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v),
+        }
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(AnyValue::as_str)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            AnyValue::String(s) => Some(s),
+            AnyValue::Map(_) => None,
+        }
+    }
+
+    pub fn entries(&self) -> &[(String, AnyValue)] {
+        match self {
+            AnyValue::String(_) => &[],
+            AnyValue::Map(entries) => entries,
+        }
+    }
+
+    /// Write the entries of a map in the layout steam uses for its own files
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        for (key, value) in self.entries() {
+            write_entry(&mut out, 0, key, value);
+        }
+        out
+    }
+}
+
+fn write_quoted(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn write_entry(out: &mut String, depth: usize, key: &str, value: &AnyValue) {
+    let indent = "\t".repeat(depth);
+    out.push_str(&indent);
+    write_quoted(out, key);
+
+    match value {
+        AnyValue::String(s) => {
+            out.push_str("\t\t");
+            write_quoted(out, s);
+            out.push('\n');
+        }
+        AnyValue::Map(entries) => {
+            out.push('\n');
+            out.push_str(&indent);
+            out.push_str("{\n");
+            for (key, value) in entries {
+                write_entry(out, depth + 1, key, value);
+            }
+            out.push_str(&indent);
+            out.push_str("}\n");
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextError {
+    pub file: Option<PathBuf>,
+    // 1 based, 0 when the error isn't about a position in the file
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl TextError {
+    fn new<S: Into<String>>(message: S) -> TextError {
+        TextError {
+            file: None,
+            line: 0,
+            column: 0,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for TextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file.display())?;
+        }
+
+        if self.line > 0 {
+            write!(f, "{}:{}: ", self.line, self.column)?;
+        }
+
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for TextError {}
+
+impl de::Error for TextError {
+    fn custom<T: Display>(msg: T) -> Self {
+        TextError::new(msg.to_string())
+    }
 }
 
-fn space(input: &str) -> IResult<&str, &str> {
-    take_while(char::is_whitespace)(input)
+impl ser::Error for TextError {
+    fn custom<T: Display>(msg: T) -> Self {
+        TextError::new(msg.to_string())
+    }
 }
 
-fn string_value(input: &str) -> IResult<&str, String> {
-    if input.len() >= 2 && &input[0..2] == "\"\"" {
-        Ok((&input[2..], String::new()))
+/// Conditions like `[$WIN32]` are checked against these names
+pub fn default_conditions() -> HashSet<String> {
+    let names: &[&str] = if cfg!(windows) {
+        &["WIN32", "WINDOWS"]
+    } else if cfg!(target_os = "macos") {
+        &["OSX", "POSIX"]
     } else {
-        let (input, value) = delimited(
-            char('"'),
-            escaped(none_of("\"\\"), '\\', one_of("\"\\")),
-            char('"'),
-        )(input)?;
+        &["LINUX", "POSIX"]
+    };
+
+    names.iter().map(|&s| s.to_owned()).collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    // quoted or not
+    String(String),
+    Open,
+    Close,
+    // the expression inside [ ]
+    Condition(String),
+}
+
+// where each value starts in the source, in the same shape as the parsed values
+#[derive(Debug)]
+struct Span {
+    offset: usize,
+    children: Vec<Span>,
+}
+
+// 1 based line and column of an offset
+fn position(src: &str, offset: usize) -> (usize, usize) {
+    let before = &src[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap().chars().count() + 1;
+    (line, column)
+}
+
+type Entries = Vec<(String, AnyValue)>;
+
+struct Parser<'a> {
+    src: &'a str,
+    offset: usize,
+    conditions: &'a HashSet<String>,
+    peeked: Option<(usize, Token)>,
+}
+
+impl<'a> Parser<'a> {
+    fn error_at<S: Into<String>>(&self, offset: usize, message: S) -> TextError {
+        let (line, column) = position(self.src, offset);
+        TextError {
+            file: None,
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.src[self.offset..]
+    }
+
+    fn skip_space(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.offset += rest.len() - trimmed.len();
+
+            if trimmed.starts_with("//") {
+                self.offset += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn quoted(&mut self) -> Result<String, TextError> {
+        let start = self.offset;
+        self.offset += 1;
+
+        let mut value = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.offset += i + 1;
+                    return Ok(value);
+                }
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, 't')) => value.push('\t'),
+                    Some((_, c)) if c == '\\' || c == '"' => value.push(c),
+                    // unknown escapes are kept as they are
+                    Some((_, c)) => {
+                        value.push('\\');
+                        value.push(c);
+                    }
+                    None => break,
+                },
+                c => value.push(c),
+            }
+        }
+
+        Err(self.error_at(start, "unterminated string"))
+    }
+
+    fn unquoted(&mut self) -> String {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| c.is_whitespace() || c == '"' || c == '{' || c == '}')
+            .unwrap_or(rest.len());
 
-        Ok((input, value.replace("\\\"", "\"").replace("\\\\", "\\")))
+        self.offset += len;
+        rest[..len].to_owned()
+    }
+
+    fn next(&mut self) -> Result<Option<(usize, Token)>, TextError> {
+        if let Some(peeked) = self.peeked.take() {
+            return Ok(Some(peeked));
+        }
+
+        self.skip_space();
+        let start = self.offset;
+
+        let token = match self.rest().chars().next() {
+            None => return Ok(None),
+            Some('{') => {
+                self.offset += 1;
+                Token::Open
+            }
+            Some('}') => {
+                self.offset += 1;
+                Token::Close
+            }
+            Some('[') => match self.rest().find(']') {
+                Some(end) => {
+                    let expression = self.rest()[1..end].trim().to_owned();
+                    self.offset += end + 1;
+                    Token::Condition(expression)
+                }
+                None => return Err(self.error_at(start, "unterminated condition")),
+            },
+            Some('"') => Token::String(self.quoted()?),
+            Some(_) => Token::String(self.unquoted()),
+        };
+
+        Ok(Some((start, token)))
+    }
+
+    fn peek(&mut self) -> Result<Option<&Token>, TextError> {
+        if self.peeked.is_none() {
+            self.peeked = self.next()?;
+        }
+
+        Ok(self.peeked.as_ref().map(|(_, t)| t))
+    }
+
+    // an optional condition after a value
+    fn condition(&mut self) -> Result<bool, TextError> {
+        match self.peek()? {
+            Some(Token::Condition(_)) => {}
+            _ => return Ok(true),
+        }
+
+        match self.next()? {
+            Some((start, Token::Condition(expression))) => {
+                eval_condition(&expression, self.conditions).ok_or_else(|| {
+                    self.error_at(start, format!("invalid condition [{}]", expression))
+                })
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // the entries of an object, up to the closing brace or the end of the file at the top level
+    fn entries(&mut self, top_level: bool) -> Result<(Entries, Vec<Span>), TextError> {
+        let mut entries = vec![];
+        let mut spans = vec![];
+
+        loop {
+            let (start, key) = match self.next()? {
+                None if top_level => return Ok((entries, spans)),
+                None => return Err(self.error_at(self.offset, "missing closing brace")),
+                Some((_, Token::Close)) if !top_level => return Ok((entries, spans)),
+                Some((start, Token::String(key))) => (start, key),
+                Some((start, token)) => {
+                    return Err(self.error_at(start, format!("expected a key, found {:?}", token)))
+                }
+            };
+
+            // a condition can also come between a key and its object
+            let key_condition = self.condition()?;
+
+            let (value, span) = match self.next()? {
+                Some((offset, Token::String(value))) => (
+                    AnyValue::String(value),
+                    Span {
+                        offset,
+                        children: vec![],
+                    },
+                ),
+                Some((offset, Token::Open)) => {
+                    let (entries, children) = self.entries(false)?;
+                    (AnyValue::Map(entries), Span { offset, children })
+                }
+                Some((at, token)) => {
+                    return Err(self.error_at(at, format!("expected a value, found {:?}", token)))
+                }
+                None => {
+                    let message = format!("missing value for {:?}", key);
+                    return Err(self.error_at(start, message));
+                }
+            };
+
+            let value_condition = self.condition()?;
+
+            if key_condition && value_condition {
+                entries.push((key, value));
+                spans.push(span);
+            }
+        }
     }
 }
 
-fn map_value(input: &str) -> IResult<&str, HashMap<String, AnyValue>> {
-    let (input, vec) = delimited(
-        char('{'),
-        delimited(space, separated_list(space, key_value), space),
-        char('}'),
-    )(input)?;
+// [$WIN32], [!$X360], [$WIN32||$OSX], [$WIN32&&!$X360]
+fn eval_condition(expression: &str, conditions: &HashSet<String>) -> Option<bool> {
+    let mut any = false;
+    for alternative in expression.split("||") {
+        let mut all = true;
+        for term in alternative.split("&&") {
+            let term = term.trim();
+            let (negate, term) = match term.strip_prefix('!') {
+                Some(rest) => (true, rest.trim_start()),
+                None => (false, term),
+            };
 
-    let map = vec.into_iter().collect();
+            let name = term.strip_prefix('$')?;
+            if name.is_empty() {
+                return None;
+            }
+
+            let defined = conditions.iter().any(|c| c.eq_ignore_ascii_case(name));
+            all &= defined != negate;
+        }
+        any |= all;
+    }
 
-    Ok((input, map))
+    Some(any)
 }
 
-pub fn key_value(input: &str) -> IResult<&str, (String, AnyValue)> {
-    separated_pair(string_value, space, any_value)(input)
+/// Parse a text key values file, returns a map of its top level entries.
+/// `#include` and `#base` are errors, use `parse_file` for files that have them
+pub fn parse(src: &str) -> Result<AnyValue, TextError> {
+    parse_with(src, &default_conditions(), &mut no_includes).map(|(root, _)| root)
 }
 
-pub fn any_value(input: &str) -> IResult<&str, AnyValue> {
-    alt((
-        map(string_value, |s| AnyValue::String(s)),
-        map(map_value, |m| AnyValue::Map(m)),
-    ))(input)
+fn no_includes(_: &str) -> Result<AnyValue, TextError> {
+    Err(TextError::new(
+        "#include and #base are only supported in files",
+    ))
+}
+
+/// Parse a text key values file, `#include` and `#base` are resolved relative to its directory
+pub fn parse_file(path: &Path) -> Result<AnyValue, TextError> {
+    parse_file_with(path, &default_conditions(), 0)
+}
+
+fn parse_file_with(
+    path: &Path,
+    conditions: &HashSet<String>,
+    depth: usize,
+) -> Result<AnyValue, TextError> {
+    // files that include each other would never finish
+    if depth > 16 {
+        return Err(TextError::new("#include nested too deeply"));
+    }
+
+    let with_file = |mut e: TextError| {
+        e.file = e.file.or_else(|| Some(path.to_owned()));
+        e
+    };
+
+    let src =
+        std::fs::read_to_string(path).map_err(|e| with_file(TextError::new(e.to_string())))?;
+
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    parse_with(&src, conditions, &mut |name| {
+        parse_file_with(&dir.join(name), conditions, depth + 1)
+    })
+    .map(|(root, _)| root)
+    .map_err(with_file)
+}
+
+fn parse_with(
+    src: &str,
+    conditions: &HashSet<String>,
+    include: &mut dyn FnMut(&str) -> Result<AnyValue, TextError>,
+) -> Result<(AnyValue, Span), TextError> {
+    let src = without_bom(src);
+    let mut includes = vec![];
+    let mut bases = vec![];
+
+    let mut parser = Parser {
+        src,
+        offset: 0,
+        conditions,
+        peeked: None,
+    };
+
+    // directives come before the entries, keys that start with # are not valid otherwise
+    loop {
+        let (start, directive) = match parser.peek()? {
+            Some(Token::String(s)) if s.starts_with('#') => {
+                let s = s.clone();
+                let start = parser.next()?.unwrap().0;
+                (start, s)
+            }
+            _ => break,
+        };
+
+        let name = match parser.next()? {
+            Some((_, Token::String(name))) => name,
+            _ => return Err(parser.error_at(start, format!("{} needs a file name", directive))),
+        };
+
+        let value = include(&name).map_err(|mut e| {
+            if e.file.is_none() {
+                let at = parser.error_at(start, "");
+                e.line = at.line;
+                e.column = at.column;
+            }
+            e
+        })?;
+
+        match directive.to_lowercase().as_str() {
+            "#include" => includes.push(value),
+            "#base" => bases.push(value),
+            _ => return Err(parser.error_at(start, format!("unknown directive {}", directive))),
+        }
+    }
+
+    let (entries, children) = parser.entries(true)?;
+    let mut root = AnyValue::Map(entries);
+
+    // included entries are added after the file's own
+    if let AnyValue::Map(entries) = &mut root {
+        for included in includes {
+            if let AnyValue::Map(more) = included {
+                entries.extend(more);
+            }
+        }
+    }
+
+    // base files only fill in keys the file doesn't have
+    for base in bases {
+        merge_base(&mut root, base);
+    }
+
+    Ok((
+        root,
+        Span {
+            offset: 0,
+            children,
+        },
+    ))
+}
+
+fn without_bom(src: &str) -> &str {
+    src.trim_start_matches('\u{feff}')
+}
+
+fn merge_base(target: &mut AnyValue, base: AnyValue) {
+    let base = match base {
+        AnyValue::Map(entries) => entries,
+        AnyValue::String(_) => return,
+    };
+
+    for (key, value) in base {
+        match target.get_mut(&key) {
+            Some(existing @ AnyValue::Map(_)) => merge_base(existing, value),
+            Some(_) => {}
+            None => {
+                if let AnyValue::Map(entries) = target {
+                    entries.push((key, value));
+                }
+            }
+        }
+    }
+}
+
+/// Deserialize a text key values file, the top level entries are the fields of `T`
+pub fn from_str<T: DeserializeOwned>(src: &str) -> Result<T, TextError> {
+    let (value, span) = parse_with(src, &default_conditions(), &mut no_includes)?;
+    T::deserialize(Located {
+        src: without_bom(src),
+        value,
+        span,
+    })
+}
+
+/// Serialize a value as a text key values file, it must serialize as a map or struct
+pub fn to_string<T: Serialize>(value: &T) -> Result<String, TextError> {
+    match value.serialize(ValueSerializer)? {
+        Some(value @ AnyValue::Map(_)) => Ok(value.to_text()),
+        _ => Err(TextError::new("top level value must be an object")),
+    }
+}
+
+impl<'de> de::Deserialize<'de> for AnyValue {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ValueVisitor;
+
+        impl<'de> Visitor<'de> for ValueVisitor {
+            type Value = AnyValue;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "a key values string or object")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<AnyValue, E> {
+                Ok(AnyValue::String(v.to_owned()))
+            }
+
+            fn visit_string<E>(self, v: String) -> Result<AnyValue, E> {
+                Ok(AnyValue::String(v))
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<AnyValue, A::Error> {
+                let mut entries = vec![];
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+
+                Ok(AnyValue::Map(entries))
+            }
+        }
+
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+impl Serialize for AnyValue {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        match self {
+            AnyValue::String(s) => serializer.serialize_str(s),
+            AnyValue::Map(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+// everything is a string in text files, so numbers and bools are parsed when asked for
+macro_rules! deserialize_parsed {
+    ( $( $method:ident $visit:ident $ty:ty ),* ) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TextError> {
+                match &self {
+                    AnyValue::String(s) => match s.trim().parse::<$ty>() {
+                        Ok(v) => visitor.$visit(v),
+                        Err(_) => Err(TextError::new(format!(
+                            "expected {}, found {:?}",
+                            stringify!($ty),
+                            s
+                        ))),
+                    },
+                    AnyValue::Map(_) => self.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for AnyValue {
+    type Error = TextError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TextError> {
+        match self {
+            AnyValue::String(s) => visitor.visit_string(s),
+            AnyValue::Map(entries) => {
+                visitor.visit_map(de::value::MapDeserializer::new(entries.into_iter()))
+            }
+        }
+    }
+
+    deserialize_parsed!(
+        deserialize_i8 visit_i8 i8,
+        deserialize_i16 visit_i16 i16,
+        deserialize_i32 visit_i32 i32,
+        deserialize_i64 visit_i64 i64,
+        deserialize_u8 visit_u8 u8,
+        deserialize_u16 visit_u16 u16,
+        deserialize_u32 visit_u32 u32,
+        deserialize_u64 visit_u64 u64,
+        deserialize_f32 visit_f32 f32,
+        deserialize_f64 visit_f64 f64
+    );
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TextError> {
+        match self.as_str().map(str::trim) {
+            Some("1") => visitor.visit_bool(true),
+            Some("0") | Some("") => visitor.visit_bool(false),
+            _ => Err(TextError::new(format!("expected a bool, found {:?}", self))),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TextError> {
+        visitor.visit_some(self)
+    }
+
+    // lists are written as objects with the keys "0", "1", ...
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TextError> {
+        match self {
+            AnyValue::Map(entries) => {
+                let values = entries.into_iter().map(|(_, v)| v);
+                visitor.visit_seq(de::value::SeqDeserializer::new(values))
+            }
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, TextError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, TextError> {
+        match self {
+            AnyValue::String(s) => visitor.visit_enum(s.into_deserializer()),
+            value => Err(TextError::new(format!(
+                "expected a variant, found {:?}",
+                value
+            ))),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        char str string bytes byte_buf unit unit_struct tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, TextError> for AnyValue {
+    type Deserializer = AnyValue;
+
+    fn into_deserializer(self) -> AnyValue {
+        self
+    }
+}
+
+// an AnyValue with where it came from in the source, errors get the position of the innermost
+// value that caused them
+struct Located<'a> {
+    src: &'a str,
+    value: AnyValue,
+    span: Span,
+}
+
+// errors from deeper values already have a position
+fn locate(src: &str, offset: usize, mut e: TextError) -> TextError {
+    if e.line == 0 {
+        let (line, column) = position(src, offset);
+        e.line = line;
+        e.column = column;
+    }
+    e
+}
+
+impl<'a> Located<'a> {
+    // entries that came from #include or #base have no span of their own, they get the parent's
+    fn entries(self) -> impl Iterator<Item = (String, Located<'a>)> {
+        let src = self.src;
+        let offset = self.span.offset;
+        let mut spans = self.span.children.into_iter();
+
+        let entries = match self.value {
+            AnyValue::Map(entries) => entries,
+            AnyValue::String(_) => vec![],
+        };
+
+        entries.into_iter().map(move |(key, value)| {
+            let span = spans.next().unwrap_or(Span {
+                offset,
+                children: vec![],
+            });
+            (key, Located { src, value, span })
+        })
+    }
+}
+
+// strings are deserialized by AnyValue, maps here so their values keep their spans
+macro_rules! deserialize_located {
+    ( $( $method:ident ( $( $arg:ident: $ty:ty ),* ) ),* ) => {
+        $(
+            fn $method<V: Visitor<'de>>(
+                self,
+                $( $arg: $ty, )*
+                visitor: V,
+            ) -> Result<V::Value, TextError> {
+                match self.value {
+                    AnyValue::Map(_) => self.deserialize_any(visitor),
+                    value => {
+                        let (src, offset) = (self.src, self.span.offset);
+                        value.$method($( $arg, )* visitor).map_err(|e| locate(src, offset, e))
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> de::Deserializer<'de> for Located<'a> {
+    type Error = TextError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TextError> {
+        let (src, offset) = (self.src, self.span.offset);
+        let result = match self.value {
+            AnyValue::String(s) => visitor.visit_string(s),
+            AnyValue::Map(_) => visitor.visit_map(de::value::MapDeserializer::new(self.entries())),
+        };
+
+        result.map_err(|e| locate(src, offset, e))
+    }
+
+    // lists are written as objects with the keys "0", "1", ...
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TextError> {
+        match self.value {
+            AnyValue::Map(_) => {
+                let (src, offset) = (self.src, self.span.offset);
+                let values = self.entries().map(|(_, v)| v);
+                let result = visitor.visit_seq(de::value::SeqDeserializer::new(values));
+                result.map_err(|e| locate(src, offset, e))
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TextError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, TextError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    deserialize_located!(
+        deserialize_i8(),
+        deserialize_i16(),
+        deserialize_i32(),
+        deserialize_i64(),
+        deserialize_u8(),
+        deserialize_u16(),
+        deserialize_u32(),
+        deserialize_u64(),
+        deserialize_f32(),
+        deserialize_f64(),
+        deserialize_bool(),
+        deserialize_enum(name: &'static str, variants: &'static [&'static str])
+    );
+
+    forward_to_deserialize_any! {
+        char str string bytes byte_buf unit unit_struct tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de, 'a> IntoDeserializer<'de, TextError> for Located<'a> {
+    type Deserializer = Located<'a>;
+
+    fn into_deserializer(self) -> Located<'a> {
+        self
+    }
+}
+
+// builds an AnyValue, None is a value that should be left out
+struct ValueSerializer;
+
+fn unsupported<T>(what: &str) -> Result<T, TextError> {
+    Err(TextError::new(format!(
+        "cannot serialize {} as a key value",
+        what
+    )))
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Option<AnyValue>;
+    type Error = TextError;
+
+    type SerializeSeq = MapBuilder;
+    type SerializeTuple = MapBuilder;
+    type SerializeTupleStruct = MapBuilder;
+    type SerializeTupleVariant = ser::Impossible<Option<AnyValue>, TextError>;
+    type SerializeMap = MapBuilder;
+    type SerializeStruct = MapBuilder;
+    type SerializeStructVariant = ser::Impossible<Option<AnyValue>, TextError>;
+
+    fn serialize_bool(self, v: bool) -> Result<Option<AnyValue>, TextError> {
+        self.serialize_str(if v { "1" } else { "0" })
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Option<AnyValue>, TextError> {
+        self.serialize_str(&v.to_string())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Option<AnyValue>, TextError> {
+        self.serialize_str(&v.to_string())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Option<AnyValue>, TextError> {
+        self.serialize_str(&v.to_string())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Option<AnyValue>, TextError> {
+        self.serialize_str(&v.to_string())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Option<AnyValue>, TextError> {
+        self.serialize_str(&v.to_string())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Option<AnyValue>, TextError> {
+        self.serialize_str(&v.to_string())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Option<AnyValue>, TextError> {
+        self.serialize_str(&v.to_string())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Option<AnyValue>, TextError> {
+        self.serialize_str(&v.to_string())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Option<AnyValue>, TextError> {
+        self.serialize_str(&v.to_string())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Option<AnyValue>, TextError> {
+        self.serialize_str(&v.to_string())
+    }
+
+    fn serialize_char(self, v: char) -> Result<Option<AnyValue>, TextError> {
+        self.serialize_str(&v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Option<AnyValue>, TextError> {
+        Ok(Some(AnyValue::String(v.to_owned())))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Option<AnyValue>, TextError> {
+        unsupported("bytes")
+    }
+
+    fn serialize_none(self) -> Result<Option<AnyValue>, TextError> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(
+        self,
+        value: &T,
+    ) -> Result<Option<AnyValue>, TextError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Option<AnyValue>, TextError> {
+        Ok(None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Option<AnyValue>, TextError> {
+        Ok(None)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Option<AnyValue>, TextError> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Option<AnyValue>, TextError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Option<AnyValue>, TextError> {
+        unsupported("an enum variant")
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<MapBuilder, TextError> {
+        Ok(MapBuilder::default())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<MapBuilder, TextError> {
+        Ok(MapBuilder::default())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<MapBuilder, TextError> {
+        Ok(MapBuilder::default())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, TextError> {
+        unsupported("an enum variant")
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapBuilder, TextError> {
+        Ok(MapBuilder::default())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<MapBuilder, TextError> {
+        Ok(MapBuilder::default())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, TextError> {
+        unsupported("an enum variant")
+    }
+}
+
+#[derive(Default)]
+struct MapBuilder {
+    entries: Vec<(String, AnyValue)>,
+    key: Option<String>,
+}
+
+impl MapBuilder {
+    fn push<T: ?Sized + Serialize>(&mut self, key: String, value: &T) -> Result<(), TextError> {
+        if let Some(value) = value.serialize(ValueSerializer)? {
+            self.entries.push((key, value));
+        }
+        Ok(())
+    }
+
+    fn element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), TextError> {
+        let key = self.entries.len().to_string();
+        self.push(key, value)
+    }
+
+    fn finish(self) -> Result<Option<AnyValue>, TextError> {
+        Ok(Some(AnyValue::Map(self.entries)))
+    }
+}
+
+impl ser::SerializeSeq for MapBuilder {
+    type Ok = Option<AnyValue>;
+    type Error = TextError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), TextError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<Option<AnyValue>, TextError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for MapBuilder {
+    type Ok = Option<AnyValue>;
+    type Error = TextError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), TextError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<Option<AnyValue>, TextError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for MapBuilder {
+    type Ok = Option<AnyValue>;
+    type Error = TextError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), TextError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<Option<AnyValue>, TextError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeMap for MapBuilder {
+    type Ok = Option<AnyValue>;
+    type Error = TextError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), TextError> {
+        match key.serialize(ValueSerializer)? {
+            Some(AnyValue::String(key)) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => unsupported("a key that isn't a string"),
+        }
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), TextError> {
+        match self.key.take() {
+            Some(key) => self.push(key, value),
+            None => Err(TextError::new("map value without a key")),
+        }
+    }
+
+    fn end(self) -> Result<Option<AnyValue>, TextError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for MapBuilder {
+    type Ok = Option<AnyValue>;
+    type Error = TextError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), TextError> {
+        self.push(key.to_owned(), value)
+    }
+
+    fn end(self) -> Result<Option<AnyValue>, TextError> {
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> AnyValue {
+        AnyValue::String(s.into())
+    }
+
+    #[test]
+    fn comments_and_unquoted_tokens() {
+        let root = parse(
+            r#"
+            // a comment before the root
+            AppState
+            {
+                appid 440 // trailing comment
+                "name"  "Team \"Fortress\" 2"
+                "path"  "C:\\Games"
+                "empty" ""
+                "dup" "a"
+                dup b
+            }
+            "#,
+        )
+        .unwrap();
+
+        let state = root.get("appstate").unwrap();
+        assert_eq!(state.get_str("appid"), Some("440"));
+        assert_eq!(state.get_str("name"), Some("Team \"Fortress\" 2"));
+        assert_eq!(state.get_str("path"), Some(r"C:\Games"));
+        assert_eq!(state.get_str("empty"), Some(""));
+
+        let keys: Vec<_> = state.entries().iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, ["appid", "name", "path", "empty", "dup", "dup"]);
+    }
+
+    #[test]
+    fn conditionals() {
+        let conditions = ["WIN32".to_owned()].iter().cloned().collect();
+        let src = r#"
+            "root"
+            {
+                "a" "windows" [$WIN32]
+                "a" "other" [!$WIN32]
+                "b" "either" [$OSX||$WIN32]
+                "c" [$X360]
+                {
+                    "d" "console"
+                }
+            }
+        "#;
+
+        let (root, _) = parse_with(src, &conditions, &mut |_| unreachable!()).unwrap();
+        let root = root.get("root").unwrap();
+        assert_eq!(
+            root,
+            &AnyValue::Map(vec![
+                ("a".into(), string("windows")),
+                ("b".into(), string("either")),
+            ])
+        );
+
+        let error = parse_with("a b [WIN32]", &conditions, &mut |_| unreachable!()).unwrap_err();
+        assert_eq!((error.line, error.column), (1, 5));
+    }
+
+    #[test]
+    fn errors_have_positions() {
+        let error = parse("\"root\"\n{\n  \"key\" \"value\n}").unwrap_err();
+        assert_eq!((error.line, error.column), (3, 9));
+        assert_eq!(error.message, "unterminated string");
+
+        let error = parse("root\n{\n  key value\n").unwrap_err();
+        assert_eq!(error.message, "missing closing brace");
+        assert_eq!(error.line, 4);
+
+        let error = parse("root\n{\n  key { } }\n}").unwrap_err();
+        assert_eq!((error.line, error.column), (4, 1));
+    }
+
+    #[test]
+    fn include_and_base() {
+        let dir = std::env::temp_dir().join(format!("vdf-text-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("base.vdf"), "root { a base b { c base } }").unwrap();
+        std::fs::write(dir.join("extra.vdf"), "extra { d included }").unwrap();
+        std::fs::write(
+            dir.join("main.vdf"),
+            "#base base.vdf\n#include \"extra.vdf\"\nroot { a main b { e main } }",
+        )
+        .unwrap();
+
+        let root = parse_file(&dir.join("main.vdf")).unwrap();
+        assert_eq!(root.get("root").unwrap().get_str("a"), Some("main"));
+        assert_eq!(
+            root.get("root").unwrap().get("b").unwrap().get_str("c"),
+            Some("base")
+        );
+        assert_eq!(root.get("extra").unwrap().get_str("d"), Some("included"));
+
+        std::fs::write(dir.join("loop.vdf"), "#include loop.vdf").unwrap();
+        assert!(parse_file(&dir.join("loop.vdf")).is_err());
+        assert!(parse("#include main.vdf").is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Manifest {
+        #[serde(rename = "AppState")]
+        app_state: AppState,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct AppState {
+        appid: u32,
+        name: String,
+        #[serde(rename = "StateFlags")]
+        state_flags: u32,
+        #[serde(default)]
+        installed: bool,
+        #[serde(default)]
+        size: Option<u64>,
+        #[serde(default)]
+        depots: Vec<String>,
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let manifest: Manifest = from_str(
+            r#"
+            "AppState"
+            {
+                "appid"     "620"
+                "name"      "Portal 2"
+                "StateFlags"    "4"
+                "installed" "1"
+                "unknown"   { "ignored" "1" }
+                "depots"    { "0" "621" "1" "622" }
+            }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            manifest.app_state,
+            AppState {
+                appid: 620,
+                name: "Portal 2".into(),
+                state_flags: 4,
+                installed: true,
+                size: None,
+                depots: vec!["621".into(), "622".into()],
+            }
+        );
+
+        let text = to_string(&manifest).unwrap();
+        assert_eq!(
+            text,
+            "\"AppState\"\n{\n\t\"appid\"\t\t\"620\"\n\t\"name\"\t\t\"Portal 2\"\n\t\"StateFlags\"\t\t\"4\"\n\t\"installed\"\t\t\"1\"\n\t\"depots\"\n\t{\n\t\t\"0\"\t\t\"621\"\n\t\t\"1\"\t\t\"622\"\n\t}\n}\n"
+        );
+        assert_eq!(from_str::<Manifest>(&text).unwrap(), manifest);
+
+        let error = from_str::<Manifest>("AppState { appid x name y StateFlags 0 }").unwrap_err();
+        assert!(error.message.contains("u32"), "{}", error);
+        assert_eq!((error.line, error.column), (1, 18));
+
+        // missing fields point at the object they're missing from
+        let error = from_str::<Manifest>("// header\nAppState\n{\n\tappid 1\n}").unwrap_err();
+        assert!(error.message.contains("name"), "{}", error);
+        assert_eq!((error.line, error.column), (3, 1));
+
+        let error = from_str::<Manifest>("AppState {\n depots { 0 { a b } }\n}").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 13));
+    }
+
+    #[test]
+    fn tree_round_trip() {
+        let src = "\"root\"\n{\n\t\"a\"\t\t\"1\"\n\t\"a\"\t\t\"C:\\\\x \\\"y\\\"\"\n\t\"child\"\n\t{\n\t}\n}\n";
+        let root = parse(src).unwrap();
+        assert_eq!(root.to_text(), src);
+        assert_eq!(to_string(&root).unwrap(), src);
+    }
 }
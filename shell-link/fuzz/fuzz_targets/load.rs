@@ -0,0 +1,18 @@
+// cargo +nightly fuzz run load fuzz/corpus/load fixtures fixtures/corrupt
+// inputs that crash go in fixtures/corrupt, once they are fixed
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use shell_link::ShellLink;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(lnk) = ShellLink::load(data) {
+        if let Some(Ok(id_list)) = lnk.id_list() {
+            id_list.location();
+        }
+        if let Some(link_info) = &lnk.link_info {
+            link_info.path();
+        }
+        lnk.app_user_model_id();
+    }
+});
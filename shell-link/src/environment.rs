@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+
+/// Environment variables for expanding strings like %windir%\\notepad.exe, or to start a
+/// process with. Names are compared without case, as windows does
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Environment {
+    // the name as it was first set, and the value, by upper case name
+    vars: HashMap<String, (String, String)>,
+}
+
+impl Environment {
+    pub fn new() -> Environment {
+        Environment::default()
+    }
+
+    /// The variables of this process
+    pub fn current() -> Environment {
+        let vars = std::env::vars_os().map(|(name, value)| {
+            let name = name.to_string_lossy().into_owned();
+            (name, value.to_string_lossy().into_owned())
+        });
+
+        vars.collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        let (_, value) = self.vars.get(&name.to_uppercase())?;
+        Some(value)
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) {
+        let var = self.vars.entry(name.to_uppercase());
+        let var = var.or_insert_with(|| (name.to_owned(), String::new()));
+        var.1 = value.to_owned();
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.vars.remove(&name.to_uppercase());
+    }
+
+    /// The names and values, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.vars
+            .values()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Replace each %NAME% with the variable, like ExpandEnvironmentStrings. Unknown
+    /// variables and a % without a pair are left as they are, there is no escape for %
+    pub fn expand(&self, value: &str) -> String {
+        let mut expanded = String::with_capacity(value.len());
+        let mut rest = value;
+
+        while let Some(start) = rest.find('%') {
+            let name = &rest[start + 1..];
+            let end = match name.find('%') {
+                Some(end) => end,
+                None => break,
+            };
+
+            expanded.push_str(&rest[..start]);
+            match self.get(&name[..end]).filter(|_| end > 0) {
+                Some(var) => expanded.push_str(var),
+                None => expanded.push_str(&rest[start..start + end + 2]),
+            }
+
+            rest = &name[end + 1..];
+        }
+
+        expanded.push_str(rest);
+        expanded
+    }
+}
+
+impl<N: AsRef<str>, V: AsRef<str>> std::iter::FromIterator<(N, V)> for Environment {
+    fn from_iter<I: IntoIterator<Item = (N, V)>>(iter: I) -> Environment {
+        let mut env = Environment::new();
+        for (name, value) in iter {
+            env.set(name.as_ref(), value.as_ref());
+        }
+
+        env
+    }
+}
@@ -0,0 +1,64 @@
+use std::fmt;
+
+/// Why a shell link couldn't be read or written, and where
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    // from the start of the file
+    pub offset: usize,
+    pub section: Section,
+    pub reason: Reason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Header,
+    IdList,
+    LinkInfo,
+    StringData,
+    ExtraData,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reason {
+    NotAShellLink,
+    UnexpectedEnd,
+    // a size field that can't hold the structure it describes
+    InvalidSize(usize),
+    // an offset outside of the structure it is in
+    InvalidOffset(usize),
+    InvalidSignature(u32),
+    UnterminatedString,
+    InvalidUtf16,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let section = match self.section {
+            Section::Header => "header",
+            Section::IdList => "id list",
+            Section::LinkInfo => "link info",
+            Section::StringData => "string data",
+            Section::ExtraData => "extra data",
+        };
+
+        write!(f, "{} at {:#x} in {}", self.reason, self.offset, section)
+    }
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::NotAShellLink => write!(f, "not a shell link"),
+            Reason::UnexpectedEnd => write!(f, "unexpected end of data"),
+            Reason::InvalidSize(size) => write!(f, "invalid size {:#x}", size),
+            Reason::InvalidOffset(offset) => write!(f, "invalid offset {:#x}", offset),
+            Reason::InvalidSignature(signature) => {
+                write!(f, "invalid signature {:#x}", signature)
+            }
+            Reason::UnterminatedString => write!(f, "unterminated string"),
+            Reason::InvalidUtf16 => write!(f, "invalid utf-16"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
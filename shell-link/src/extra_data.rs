@@ -0,0 +1,484 @@
+use crate::{
+    decode_ansi, encode_ansi,
+    reader::Reader,
+    writer::{utf16_bytes, Writer},
+    Error, Guid, Reason,
+};
+
+pub const ENVIRONMENT_VARIABLES: u32 = 0xA000_0001;
+pub const CONSOLE: u32 = 0xA000_0002;
+pub const TRACKER: u32 = 0xA000_0003;
+pub const SPECIAL_FOLDER: u32 = 0xA000_0005;
+pub const DARWIN: u32 = 0xA000_0006;
+pub const ICON_ENVIRONMENT: u32 = 0xA000_0007;
+pub const PROPERTY_STORE: u32 = 0xA000_0009;
+pub const KNOWN_FOLDER: u32 = 0xA000_000B;
+
+/// The blocks after the string data, in the order they were stored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtraData {
+    EnvironmentVariables(ExpString),
+    Console(Console),
+    Tracker(Tracker),
+    SpecialFolder(SpecialFolder),
+    // the descriptor of an msi advertised shortcut
+    Darwin(ExpString),
+    IconEnvironment(ExpString),
+    PropertyStore(Vec<PropertyStorage>),
+    KnownFolder(KnownFolder),
+    Unknown { signature: u32, data: Vec<u8> },
+}
+
+/// A string block with room for 260 characters, stored in both the ansi code page and utf-16
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpString {
+    pub ansi: String,
+    pub unicode: String,
+}
+
+impl ExpString {
+    pub fn value(&self) -> &str {
+        match self.unicode.is_empty() {
+            true => &self.ansi,
+            false => &self.unicode,
+        }
+    }
+}
+
+/// Settings for the console window of console applications
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Console {
+    pub fill_attributes: u16,
+    pub popup_fill_attributes: u16,
+    pub screen_buffer_size: (i16, i16),
+    pub window_size: (i16, i16),
+    pub window_origin: (i16, i16),
+    pub font_size: u32,
+    pub font_family: u32,
+    pub font_weight: u32,
+    pub face_name: String,
+    pub cursor_size: u32,
+    pub full_screen: bool,
+    pub quick_edit: bool,
+    pub insert_mode: bool,
+    pub auto_position: bool,
+    pub history_buffer_size: u32,
+    pub number_of_history_buffers: u32,
+    pub history_no_dup: bool,
+    pub color_table: [u32; 16],
+}
+
+/// What the distributed link tracking service needs to find a moved target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tracker {
+    pub version: u32,
+    // netbios name of the machine the target was last seen on
+    pub machine_id: String,
+    // volume and object ids, now and when the target was created
+    pub droid: [Guid; 2],
+    pub droid_birth: [Guid; 2],
+}
+
+/// The id list item that starts a special folder, by CSIDL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpecialFolder {
+    pub folder_id: u32,
+    // byte offset of the item in the id list
+    pub offset: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnownFolder {
+    pub folder: Guid,
+    // byte offset of the item in the id list
+    pub offset: u32,
+}
+
+/// Serialized property storage, the properties of one format id
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropertyStorage {
+    pub format_id: Guid,
+    pub properties: Vec<(PropertyId, PropertyValue)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PropertyId {
+    Id(u32),
+    Name(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PropertyValue {
+    String(String),
+    I32(i32),
+    U32(u32),
+    I64(i64),
+    U64(u64),
+    Bool(bool),
+    FileTime(u64),
+    Guid(Guid),
+    // any other VARTYPE, with the bytes after the type
+    Other { ty: u16, data: Vec<u8> },
+}
+
+// properties of this format id are named by strings instead of numbers
+const NAMED_PROPERTIES: Guid = Guid::from_u128(0xD5CDD505_2E9C_101B_9397_08002B2CF9AE);
+
+const SERIALIZED_PROPERTY_STORAGE: u32 = 0x5350_5331;
+
+const VT_I4: u16 = 0x03;
+const VT_BSTR: u16 = 0x08;
+const VT_BOOL: u16 = 0x0B;
+const VT_I8: u16 = 0x14;
+const VT_UI8: u16 = 0x15;
+const VT_UI4: u16 = 0x13;
+const VT_LPWSTR: u16 = 0x1F;
+const VT_FILETIME: u16 = 0x40;
+const VT_CLSID: u16 = 0x48;
+
+impl ExpString {
+    fn load(data: Reader) -> Result<ExpString, Error> {
+        let ansi = data.bytes(0, 260)?;
+        let ansi_len = ansi.iter().position(|b| *b == 0).unwrap_or(260);
+
+        Ok(ExpString {
+            ansi: decode_ansi(&ansi[..ansi_len]),
+            unicode: data.utf16_at(260, 260)?,
+        })
+    }
+
+    pub fn new(value: &str) -> ExpString {
+        ExpString {
+            ansi: value.to_owned(),
+            unicode: value.to_owned(),
+        }
+    }
+
+    fn store(&self, data: &mut Vec<u8>) {
+        data.write_fixed(&encode_ansi(&self.ansi), 260);
+        data.write_fixed(&utf16_bytes(&self.unicode), 520);
+    }
+}
+
+impl Console {
+    fn load(data: Reader) -> Result<Console, Error> {
+        let i16_at = |offset| Ok::<_, Error>(data.u16_at(offset)? as i16);
+        let pair_at = |offset| Ok::<_, Error>((i16_at(offset)?, i16_at(offset + 2)?));
+        let bool_at = |offset| Ok::<_, Error>(data.u32_at(offset)? != 0);
+
+        let mut color_table = [0; 16];
+        for (i, color) in color_table.iter_mut().enumerate() {
+            *color = data.u32_at(132 + i * 4)?;
+        }
+
+        Ok(Console {
+            fill_attributes: data.u16_at(0)?,
+            popup_fill_attributes: data.u16_at(2)?,
+            screen_buffer_size: pair_at(4)?,
+            window_size: pair_at(8)?,
+            window_origin: pair_at(12)?,
+            // two unused fields
+            font_size: data.u32_at(24)?,
+            font_family: data.u32_at(28)?,
+            font_weight: data.u32_at(32)?,
+            face_name: data.utf16_at(36, 32)?,
+            cursor_size: data.u32_at(100)?,
+            full_screen: bool_at(104)?,
+            quick_edit: bool_at(108)?,
+            insert_mode: bool_at(112)?,
+            auto_position: bool_at(116)?,
+            history_buffer_size: data.u32_at(120)?,
+            number_of_history_buffers: data.u32_at(124)?,
+            history_no_dup: bool_at(128)?,
+            color_table,
+        })
+    }
+
+    fn store(&self, data: &mut Vec<u8>) {
+        let pairs = [
+            self.screen_buffer_size,
+            self.window_size,
+            self.window_origin,
+        ];
+
+        data.write_u16(self.fill_attributes);
+        data.write_u16(self.popup_fill_attributes);
+        for (x, y) in pairs.iter() {
+            data.write_u16(*x as u16);
+            data.write_u16(*y as u16);
+        }
+        data.write_u32(0);
+        data.write_u32(0);
+        data.write_u32(self.font_size);
+        data.write_u32(self.font_family);
+        data.write_u32(self.font_weight);
+        data.write_fixed(&utf16_bytes(&self.face_name), 64);
+        data.write_u32(self.cursor_size);
+        data.write_u32(self.full_screen as u32);
+        data.write_u32(self.quick_edit as u32);
+        data.write_u32(self.insert_mode as u32);
+        data.write_u32(self.auto_position as u32);
+        data.write_u32(self.history_buffer_size);
+        data.write_u32(self.number_of_history_buffers);
+        data.write_u32(self.history_no_dup as u32);
+        for color in self.color_table.iter() {
+            data.write_u32(*color);
+        }
+    }
+}
+
+impl Tracker {
+    fn load(data: Reader) -> Result<Tracker, Error> {
+        let machine_id = data.bytes(8, 16)?;
+        let machine_id_len = machine_id.iter().position(|b| *b == 0).unwrap_or(16);
+
+        Ok(Tracker {
+            version: data.u32_at(4)?,
+            machine_id: decode_ansi(&machine_id[..machine_id_len]),
+            droid: [data.guid_at(24)?, data.guid_at(40)?],
+            droid_birth: [data.guid_at(56)?, data.guid_at(72)?],
+        })
+    }
+
+    fn store(&self, data: &mut Vec<u8>) {
+        data.write_u32(0x58);
+        data.write_u32(self.version);
+        data.write_fixed(&encode_ansi(&self.machine_id), 16);
+        for guid in self.droid.iter().chain(self.droid_birth.iter()) {
+            data.write_guid(*guid);
+        }
+    }
+}
+
+impl PropertyValue {
+    fn load(data: Reader) -> Result<PropertyValue, Error> {
+        let ty = data.u16_at(0)?;
+        let value = data.from(4)?;
+
+        let value = match ty {
+            VT_LPWSTR => PropertyValue::String(value.utf16_at(4, value.u32_at(0)? as usize)?),
+            VT_BSTR => PropertyValue::String(value.utf16_at(4, value.u32_at(0)? as usize / 2)?),
+            VT_I4 => PropertyValue::I32(value.u32_at(0)? as i32),
+            VT_UI4 => PropertyValue::U32(value.u32_at(0)?),
+            VT_I8 => PropertyValue::I64(value.u64_at(0)? as i64),
+            VT_UI8 => PropertyValue::U64(value.u64_at(0)?),
+            VT_BOOL => PropertyValue::Bool(value.u16_at(0)? != 0),
+            VT_FILETIME => PropertyValue::FileTime(value.u64_at(0)?),
+            VT_CLSID => PropertyValue::Guid(value.guid_at(0)?),
+            ty => PropertyValue::Other {
+                ty,
+                data: value.bytes(0, value.len())?.to_vec(),
+            },
+        };
+
+        Ok(value)
+    }
+
+    fn store(&self, data: &mut Vec<u8>) {
+        let (ty, value) = match self {
+            PropertyValue::String(value) => {
+                let mut chars = utf16_bytes(value);
+                chars.extend_from_slice(&[0, 0]);
+
+                let mut typed = vec![];
+                typed.write_u32((chars.len() / 2) as u32);
+                typed.extend(chars);
+                (VT_LPWSTR, typed)
+            }
+            PropertyValue::I32(value) => (VT_I4, value.to_le_bytes().to_vec()),
+            PropertyValue::U32(value) => (VT_UI4, value.to_le_bytes().to_vec()),
+            PropertyValue::I64(value) => (VT_I8, value.to_le_bytes().to_vec()),
+            PropertyValue::U64(value) => (VT_UI8, value.to_le_bytes().to_vec()),
+            // VARIANT_TRUE is all bits set
+            PropertyValue::Bool(value) => (VT_BOOL, (-(*value as i16)).to_le_bytes().to_vec()),
+            PropertyValue::FileTime(value) => (VT_FILETIME, value.to_le_bytes().to_vec()),
+            PropertyValue::Guid(value) => (VT_CLSID, value.0.to_vec()),
+            PropertyValue::Other { ty, data: value } => {
+                data.write_u16(*ty);
+                data.write_u16(0);
+                data.extend_from_slice(value);
+                return;
+            }
+        };
+
+        data.write_u16(ty);
+        data.write_u16(0);
+        data.extend(value);
+        // the value is padded to 4 bytes, kept as it was for other types
+        data.resize((data.len() + 3) & !3, 0);
+    }
+}
+
+impl PropertyStorage {
+    fn load(data: Reader) -> Result<PropertyStorage, Error> {
+        let format_id = data.guid_at(8)?;
+        let named = format_id == NAMED_PROPERTIES;
+
+        let mut properties = vec![];
+        let mut offset = 24;
+        loop {
+            let size = data.u32_at(offset)? as usize;
+            match size {
+                0 => break,
+                1..=12 => return Err(data.error(offset, Reason::InvalidSize(size))),
+                _ => {}
+            }
+
+            let value = data.sub(offset, size)?;
+            let property = if named {
+                let name_size = value.u32_at(4)? as usize;
+                let name = value.utf16_at(9, name_size / 2)?;
+                let typed = value.from(9usize.saturating_add(name_size))?;
+                (PropertyId::Name(name), PropertyValue::load(typed)?)
+            } else {
+                let id = value.u32_at(4)?;
+                (PropertyId::Id(id), PropertyValue::load(value.from(9)?)?)
+            };
+
+            properties.push(property);
+            offset += size;
+        }
+
+        Ok(PropertyStorage {
+            format_id,
+            properties,
+        })
+    }
+
+    fn store(&self, out: &mut Vec<u8>) {
+        let mut data = vec![];
+        data.write_u32(0);
+        data.write_u32(SERIALIZED_PROPERTY_STORAGE);
+        data.write_guid(self.format_id);
+
+        for (id, value) in &self.properties {
+            let mut property = vec![];
+            property.write_u32(0);
+            match id {
+                PropertyId::Id(id) => {
+                    property.write_u32(*id);
+                    property.push(0);
+                }
+                PropertyId::Name(name) => {
+                    property.write_u32(utf16_bytes(name).len() as u32 + 2);
+                    property.push(0);
+                    property.write_unicode(name);
+                }
+            }
+
+            let mut typed = vec![];
+            value.store(&mut typed);
+            property.extend(typed);
+            property.write_size();
+            data.extend(property);
+        }
+
+        data.write_u32(0);
+        data.write_size();
+        out.extend(data);
+    }
+}
+
+fn load_property_store(data: Reader) -> Result<Vec<PropertyStorage>, Error> {
+    let mut storages = vec![];
+
+    let mut offset = 0;
+    loop {
+        let size = data.u32_at(offset)? as usize;
+        if size == 0 {
+            break;
+        }
+
+        let storage = data.from(offset)?.sized(24)?;
+        if storage.u32_at(4)? != SERIALIZED_PROPERTY_STORAGE {
+            let signature = storage.u32_at(4)?;
+            return Err(storage.error(4, Reason::InvalidSignature(signature)));
+        }
+
+        storages.push(PropertyStorage::load(storage)?);
+        offset += size;
+    }
+
+    Ok(storages)
+}
+
+fn store_property_store(storages: &[PropertyStorage], data: &mut Vec<u8>) {
+    for storage in storages {
+        storage.store(data);
+    }
+    data.write_u32(0);
+}
+
+impl ExtraData {
+    /// Parse one block, `data` is what follows its size and signature
+    pub(crate) fn load(signature: u32, data: Reader) -> Result<ExtraData, Error> {
+        let size = data.len() + 8;
+        let block = match (signature, size) {
+            (ENVIRONMENT_VARIABLES, 0x314) => {
+                ExtraData::EnvironmentVariables(ExpString::load(data)?)
+            }
+            (CONSOLE, 0xCC) => ExtraData::Console(Console::load(data)?),
+            (TRACKER, 0x60) => ExtraData::Tracker(Tracker::load(data)?),
+            (SPECIAL_FOLDER, 0x10) => ExtraData::SpecialFolder(SpecialFolder {
+                folder_id: data.u32_at(0)?,
+                offset: data.u32_at(4)?,
+            }),
+            (DARWIN, 0x314) => ExtraData::Darwin(ExpString::load(data)?),
+            (ICON_ENVIRONMENT, 0x314) => ExtraData::IconEnvironment(ExpString::load(data)?),
+            (PROPERTY_STORE, _) => ExtraData::PropertyStore(load_property_store(data)?),
+            (KNOWN_FOLDER, 0x1C) => ExtraData::KnownFolder(KnownFolder {
+                folder: data.guid_at(0)?,
+                offset: data.u32_at(16)?,
+            }),
+            // blocks of an unexpected size are kept as they are
+            (signature, _) => ExtraData::Unknown {
+                signature,
+                data: data.bytes(0, data.len())?.to_vec(),
+            },
+        };
+
+        Ok(block)
+    }
+
+    pub fn signature(&self) -> u32 {
+        match self {
+            ExtraData::EnvironmentVariables(_) => ENVIRONMENT_VARIABLES,
+            ExtraData::Console(_) => CONSOLE,
+            ExtraData::Tracker(_) => TRACKER,
+            ExtraData::SpecialFolder(_) => SPECIAL_FOLDER,
+            ExtraData::Darwin(_) => DARWIN,
+            ExtraData::IconEnvironment(_) => ICON_ENVIRONMENT,
+            ExtraData::PropertyStore(_) => PROPERTY_STORE,
+            ExtraData::KnownFolder(_) => KNOWN_FOLDER,
+            ExtraData::Unknown { signature, .. } => *signature,
+        }
+    }
+
+    /// Write the block with its size and signature
+    pub(crate) fn store(&self, out: &mut Vec<u8>) {
+        let mut data = vec![];
+        data.write_u32(0);
+        data.write_u32(self.signature());
+
+        match self {
+            ExtraData::EnvironmentVariables(value) => value.store(&mut data),
+            ExtraData::Console(console) => console.store(&mut data),
+            ExtraData::Tracker(tracker) => tracker.store(&mut data),
+            ExtraData::SpecialFolder(folder) => {
+                data.write_u32(folder.folder_id);
+                data.write_u32(folder.offset);
+            }
+            ExtraData::Darwin(value) => value.store(&mut data),
+            ExtraData::IconEnvironment(value) => value.store(&mut data),
+            ExtraData::PropertyStore(storages) => store_property_store(storages, &mut data),
+            ExtraData::KnownFolder(folder) => {
+                data.write_guid(folder.folder);
+                data.write_u32(folder.offset);
+            }
+            ExtraData::Unknown { data: block, .. } => data.extend_from_slice(block),
+        }
+
+        data.write_size();
+        out.extend(data);
+    }
+}
@@ -0,0 +1,43 @@
+use std::fmt;
+
+/// A guid as it is laid out in the file, with the first three fields little endian
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Guid(pub [u8; 16]);
+
+impl Guid {
+    /// From the usual text form read as one number, {20D04FE0-3AEA-...} is 0x20D04FE0_3AEA_...
+    pub const fn from_u128(value: u128) -> Guid {
+        let b = value.to_be_bytes();
+        Guid([
+            b[3], b[2], b[1], b[0], b[5], b[4], b[7], b[6], b[8], b[9], b[10], b[11], b[12], b[13],
+            b[14], b[15],
+        ])
+    }
+
+    pub fn from_slice(data: &[u8]) -> Guid {
+        let mut raw = [0; 16];
+        raw.copy_from_slice(&data[..16]);
+        Guid(raw)
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let b = &self.0;
+        write!(
+            f,
+            "{{{:02X}{:02X}{:02X}{:02X}-{:02X}{:02X}-{:02X}{:02X}-{:02X}{:02X}-",
+            b[3], b[2], b[1], b[0], b[5], b[4], b[7], b[6], b[8], b[9]
+        )?;
+        for byte in &b[10..] {
+            write!(f, "{:02X}", byte)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+impl fmt::Debug for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
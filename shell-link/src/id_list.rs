@@ -0,0 +1,224 @@
+use crate::{reader::Reader, Error, Guid, Reason};
+
+/// Shell folders that show up at the root of id lists
+pub mod folders {
+    use crate::Guid;
+
+    pub const MY_COMPUTER: Guid = Guid::from_u128(0x20D04FE0_3AEA_1069_A2D8_08002B30309D);
+    pub const CONTROL_PANEL: Guid = Guid::from_u128(0x21EC2020_3AEA_1069_A2DD_08002B30309D);
+    pub const ALL_CONTROL_PANEL_ITEMS: Guid =
+        Guid::from_u128(0x26EE0668_A00A_44D7_9371_BEB064C98683);
+    pub const RECYCLE_BIN: Guid = Guid::from_u128(0x645FF040_5081_101B_9F08_00AA002F954E);
+    pub const NETWORK: Guid = Guid::from_u128(0xF02C1A0D_BE21_4350_88B0_7367FC96EF3C);
+    pub const USERS_FILES: Guid = Guid::from_u128(0x59031A47_3F72_44A7_89C5_5595FE6B30EE);
+    pub const INTERNET: Guid = Guid::from_u128(0x871C5380_42A0_1069_A2EA_08002B30309D);
+
+    // known folders under my computer
+    pub const DESKTOP: Guid = Guid::from_u128(0xB4BFCC3A_DB2C_424C_B029_7FE99A87C641);
+    pub const DOCUMENTS: Guid = Guid::from_u128(0xD3162B92_9365_467A_956B_92703ACA08AF);
+    pub const DOWNLOADS: Guid = Guid::from_u128(0x088E3905_0323_4B02_9826_5D99428E115F);
+    pub const MUSIC: Guid = Guid::from_u128(0x3DFDF296_DBEC_4FB4_81D1_6A3438BCF4DE);
+    pub const PICTURES: Guid = Guid::from_u128(0x24AD3AD4_A569_4530_98E1_AB02F9417AA8);
+    pub const VIDEOS: Guid = Guid::from_u128(0xF86FA3AB_70D2_4FC7_9C99_FCBF05467F3A);
+}
+
+/// One entry of an id list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShellItem {
+    // a shell folder, at the root or inside my computer
+    Folder(Guid),
+    // "C:\"
+    Drive(String),
+    File(FileEntry),
+    Uri(String),
+    Other { class: u8, data: Vec<u8> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEntry {
+    pub is_directory: bool,
+    pub file_size: u32,
+    pub attributes: u16,
+    // fat date and time, the date in the low word
+    pub modified: u32,
+    pub created: Option<u32>,
+    pub accessed: Option<u32>,
+    // the 8.3 name, unless the item was written by a unicode aware shell
+    pub short_name: String,
+    pub long_name: Option<String>,
+}
+
+impl FileEntry {
+    pub fn name(&self) -> &str {
+        self.long_name.as_deref().unwrap_or(&self.short_name)
+    }
+}
+
+/// What an id list points to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location {
+    Path(String),
+    Uri(String),
+    // a folder without a file system path of its own, and the names of the items below it
+    Virtual { folder: Guid, items: Vec<String> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdList(pub Vec<ShellItem>);
+
+const BEEF0004: u32 = 0xBEEF_0004;
+
+impl FileEntry {
+    fn load(data: Reader) -> Result<FileEntry, Error> {
+        let class = data.u8_at(2)?;
+        let unicode = class & 0x04 != 0;
+
+        let (short_name, name_end) = if unicode {
+            let name = data.unicode_at(14)?;
+            let end = 14 + (name.encode_utf16().count() + 1) * 2;
+            (name, end)
+        } else {
+            // one byte per character in the ansi code page
+            let name = data.ansi_at(14)?;
+            let end = 14 + name.chars().count() + 1;
+            (name, end + end % 2)
+        };
+
+        let mut entry = FileEntry {
+            is_directory: class & 0x01 != 0,
+            file_size: data.u32_at(4)?,
+            modified: data.u32_at(8)?,
+            attributes: data.u16_at(12)?,
+            created: None,
+            accessed: None,
+            short_name,
+            long_name: None,
+        };
+
+        // extension blocks follow the name, each starting with its size, version and signature
+        let mut offset = name_end;
+        while offset + 8 <= data.len() {
+            let size = data.u16_at(offset)? as usize;
+            if size < 8 || offset + size > data.len() {
+                break;
+            }
+
+            let block = data.sub(offset, size)?;
+            if block.u32_at(4)? == BEEF0004 {
+                entry.load_beef0004(block)?;
+            }
+
+            offset += size;
+        }
+
+        Ok(entry)
+    }
+
+    fn load_beef0004(&mut self, block: Reader) -> Result<(), Error> {
+        let version = block.u16_at(2)?;
+        self.created = Some(block.u32_at(8)?);
+        self.accessed = Some(block.u32_at(12)?);
+
+        let mut offset = 18;
+        if version >= 7 {
+            // padding, mft reference and an unknown field
+            offset += 18;
+        }
+        if version >= 3 {
+            // size of the localized name
+            offset += 2;
+        }
+        if version >= 9 {
+            offset += 4;
+        }
+        if version >= 8 {
+            offset += 4;
+        }
+
+        if version >= 3 && offset < block.len() {
+            self.long_name = Some(block.unicode_at(offset)?);
+        }
+
+        Ok(())
+    }
+}
+
+impl ShellItem {
+    fn load(data: Reader) -> Result<ShellItem, Error> {
+        let class = data.u8_at(2)?;
+        let item = match class {
+            0x1F | 0x2E => ShellItem::Folder(data.guid_at(4)?),
+            0x20..=0x2F => ShellItem::Drive(data.ansi_at(3)?),
+            0x30..=0x3F => ShellItem::File(FileEntry::load(data)?),
+            0x61 => {
+                let flags = data.u8_at(3)?;
+                let offset = 6 + data.u16_at(4)? as usize;
+                match flags & 0x80 {
+                    0 => ShellItem::Uri(data.ansi_at(offset)?),
+                    _ => ShellItem::Uri(data.unicode_at(offset)?),
+                }
+            }
+            _ => ShellItem::Other {
+                class,
+                data: data.bytes(3, data.len() - 3)?.to_vec(),
+            },
+        };
+
+        Ok(item)
+    }
+}
+
+impl IdList {
+    /// Parse the items of an id list, `data` starts after its size field
+    pub(crate) fn load(data: Reader) -> Result<IdList, Error> {
+        let mut items = vec![];
+
+        let mut offset = 0;
+        loop {
+            let size = data.u16_at(offset)? as usize;
+            match size {
+                0 => break,
+                1..=2 => return Err(data.error(offset, Reason::InvalidSize(size))),
+                _ => {}
+            }
+
+            items.push(ShellItem::load(data.sub(offset, size)?)?);
+            offset += size;
+        }
+
+        Ok(IdList(items))
+    }
+
+    pub fn location(&self) -> Option<Location> {
+        let mut folder = None;
+        let mut path: Option<String> = None;
+        let mut items = vec![];
+
+        for item in &self.0 {
+            match item {
+                ShellItem::Folder(guid) => {
+                    folder = Some(*guid);
+                    items.clear();
+                }
+                ShellItem::Drive(drive) => path = Some(drive.clone()),
+                ShellItem::File(entry) => match &mut path {
+                    Some(path) => {
+                        if !path.ends_with('\\') {
+                            path.push('\\');
+                        }
+                        path.push_str(entry.name());
+                    }
+                    None => items.push(entry.name().to_owned()),
+                },
+                ShellItem::Uri(uri) => return Some(Location::Uri(uri.clone())),
+                ShellItem::Other { .. } => {}
+            }
+        }
+
+        match (path, folder) {
+            (Some(path), _) => Some(Location::Path(path)),
+            (None, Some(folder)) => Some(Location::Virtual { folder, items }),
+            (None, None) => None,
+        }
+    }
+}
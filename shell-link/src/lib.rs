@@ -0,0 +1,949 @@
+#[macro_use]
+extern crate flat;
+
+use bitflags::bitflags;
+use flat::prelude::*;
+use std::convert::TryFrom;
+
+mod environment;
+pub use environment::*;
+
+mod error;
+pub use error::*;
+
+mod extra_data;
+pub use extra_data::*;
+
+mod guid;
+pub use guid::*;
+
+mod id_list;
+pub use id_list::*;
+
+mod link_info;
+pub use link_info::*;
+
+mod reader;
+use reader::Reader;
+
+mod writer;
+use writer::{utf16_bytes, Writer};
+
+pub struct ShellLink {
+    pub link_flags: LinkFlags,
+    pub file_attributes: u32,
+    pub creation_time: u64,
+    pub access_time: u64,
+    pub write_time: u64,
+    pub file_size: u32,
+    pub icon_index: i32,
+    pub show_command: u32,
+    pub hotkey: u16,
+
+    pub link_target_id_list: Option<Vec<u8>>,
+    pub link_info: Option<LinkInfo>,
+
+    pub name: Option<String>,
+    pub relative_path: Option<String>,
+    pub working_dir: Option<String>,
+    pub command_line_arguments: Option<String>,
+    pub icon_location: Option<String>,
+
+    pub extra_data: Vec<ExtraData>,
+}
+
+// the values of show_command, anything else is read as normal
+pub const SW_SHOWNORMAL: u32 = 1;
+pub const SW_SHOWMAXIMIZED: u32 = 3;
+pub const SW_SHOWMINNOACTIVE: u32 = 7;
+
+const LNK_CLSID: Guid = Guid::from_u128(0x00021401_0000_0000_C000_000000000046);
+const APP_USER_MODEL: Guid = Guid::from_u128(0x9F4C2855_9F79_4B39_A8D0_E1D42DE1D5F3);
+
+// windows-1252 for 0x80..0xA0, where it differs from latin-1
+const CP1252_HIGH: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž', '\u{8f}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}', 'ž', 'Ÿ',
+];
+
+/// Decode a string in the ansi code page. The code page isn't stored in the link, so this
+/// assumes windows-1252, which is right for western systems
+pub fn decode_ansi(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&b| match b {
+            0x80..=0x9f => CP1252_HIGH[b as usize - 0x80],
+            b => b as char,
+        })
+        .collect()
+}
+
+/// Encode a string in the ansi code page, characters that aren't in windows-1252 become ?
+pub fn encode_ansi(value: &str) -> Vec<u8> {
+    value
+        .chars()
+        .map(|c| match c as u32 {
+            0..=0x7f | 0xa0..=0xff => c as u8,
+            _ => match CP1252_HIGH.iter().position(|&high| high == c) {
+                Some(i) => 0x80 + i as u8,
+                None => b'?',
+            },
+        })
+        .collect()
+}
+
+impl ShellLink {
+    /// A link to a local path or one on a share, with only the link info windows needs to
+    /// find it
+    pub fn new(target: &str) -> ShellLink {
+        ShellLink {
+            link_flags: LinkFlags::IS_UNICODE,
+            file_attributes: 0,
+            creation_time: 0,
+            access_time: 0,
+            write_time: 0,
+            file_size: 0,
+            icon_index: 0,
+            show_command: SW_SHOWNORMAL,
+            hotkey: 0,
+            link_target_id_list: None,
+            link_info: Some(LinkInfo::from_path(target)),
+            name: None,
+            relative_path: None,
+            working_dir: None,
+            command_line_arguments: None,
+            icon_location: None,
+            extra_data: vec![],
+        }
+    }
+
+    pub fn id_list(&self) -> Option<Result<IdList, Error>> {
+        // the list starts after the header and its size
+        let data = self.link_target_id_list.as_deref()?;
+        Some(IdList::load(Reader::new(data, 0x4E, Section::IdList)))
+    }
+
+    /// Where the link points, without asking the shell. The caller has to find virtual
+    /// locations, they move between machines
+    pub fn target(&self, env: &Environment) -> Option<Location> {
+        if let Some(path) = self.link_info.as_ref().and_then(LinkInfo::path) {
+            return Some(Location::Path(path));
+        } else if let Some(path) = self.environment_variable_data() {
+            return Some(Location::Path(env.expand(path)));
+        }
+
+        self.id_list()?.ok()?.location()
+    }
+
+    /// The file with the icon, if the link names one
+    pub fn icon_path(&self, env: &Environment) -> Option<String> {
+        let path = self.icon_location.as_deref();
+        let path = path.or_else(|| self.icon_environment_data())?;
+        Some(env.expand(path))
+    }
+
+    /// The hotkey the way the properties of a shortcut show it, like Ctrl + Alt + N
+    pub fn hotkey_name(&self) -> Option<String> {
+        let key = (self.hotkey & 0xFF) as u8;
+        let modifiers = (self.hotkey >> 8) as u8;
+
+        // the keys windows allows, as virtual key codes
+        let key = match key {
+            b'0'..=b'9' | b'A'..=b'Z' => (key as char).to_string(),
+            0x70..=0x87 => format!("F{}", key - 0x6F),
+            0x90 => "Num Lock".into(),
+            0x91 => "Scroll Lock".into(),
+            _ => return None,
+        };
+
+        let names = [(0x02, "Ctrl"), (0x04, "Alt"), (0x01, "Shift")];
+        let mut parts: Vec<_> = names
+            .iter()
+            .filter(|(flag, _)| modifiers & flag != 0)
+            .map(|(_, name)| name.to_string())
+            .collect();
+
+        parts.push(key);
+        Some(parts.join(" + "))
+    }
+
+    /// The target path with environment variables, like %windir%\\notepad.exe
+    pub fn environment_variable_data(&self) -> Option<&str> {
+        self.extra_data.iter().find_map(|block| match block {
+            ExtraData::EnvironmentVariables(value) => Some(value.value()),
+            _ => None,
+        })
+    }
+
+    pub fn icon_environment_data(&self) -> Option<&str> {
+        self.extra_data.iter().find_map(|block| match block {
+            ExtraData::IconEnvironment(value) => Some(value.value()),
+            _ => None,
+        })
+    }
+
+    pub fn property(&self, format_id: Guid, id: u32) -> Option<&PropertyValue> {
+        let storages = self.extra_data.iter().filter_map(|block| match block {
+            ExtraData::PropertyStore(storages) => Some(storages),
+            _ => None,
+        });
+
+        storages
+            .flatten()
+            .filter(|storage| storage.format_id == format_id)
+            .flat_map(|storage| &storage.properties)
+            .find(|(key, _)| *key == PropertyId::Id(id))
+            .map(|(_, value)| value)
+    }
+
+    /// System.AppUserModel.ID, which ties the link to a packaged app or taskbar group
+    pub fn app_user_model_id(&self) -> Option<&str> {
+        match self.property(APP_USER_MODEL, 5)? {
+            PropertyValue::String(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Add the property to the property store, or replace it if it's there already
+    pub fn set_property(&mut self, format_id: Guid, id: u32, value: PropertyValue) {
+        let block = self
+            .extra_data
+            .iter()
+            .position(|block| matches!(block, ExtraData::PropertyStore(_)));
+
+        let block = block.unwrap_or_else(|| {
+            self.extra_data.push(ExtraData::PropertyStore(vec![]));
+            self.extra_data.len() - 1
+        });
+
+        let storages = match &mut self.extra_data[block] {
+            ExtraData::PropertyStore(storages) => storages,
+            _ => unreachable!(),
+        };
+
+        let storage = match storages.iter().position(|s| s.format_id == format_id) {
+            Some(i) => &mut storages[i],
+            None => {
+                storages.push(PropertyStorage {
+                    format_id,
+                    properties: vec![],
+                });
+                storages.last_mut().unwrap()
+            }
+        };
+
+        let key = PropertyId::Id(id);
+        match storage.properties.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, old)) => *old = value,
+            None => storage.properties.push((key, value)),
+        }
+    }
+
+    pub fn set_app_user_model_id(&mut self, id: &str) {
+        self.set_property(APP_USER_MODEL, 5, PropertyValue::String(id.to_owned()));
+    }
+
+    fn load_string_data(data: Reader, offset: &mut usize, unicode: bool) -> Result<String, Error> {
+        let len = data.u16_at(*offset)? as usize;
+        let start = *offset + 2;
+
+        let value = if unicode {
+            *offset = start + len * 2;
+            data.utf16_at(start, len)?
+        } else {
+            *offset = start + len;
+            decode_ansi(data.bytes(start, len)?)
+        };
+
+        Ok(value)
+    }
+
+    pub fn load(src: &[u8]) -> Result<ShellLink, Error> {
+        let data = Reader::new(src, 0, Section::Header);
+        let header_data = data.bytes(0, std::mem::size_of::<ShellLinkHeader>())?;
+        if data.u32_at(0)? != 0x4C || data.guid_at(4)? != LNK_CLSID {
+            return Err(data.error(0, Reason::NotAShellLink));
+        }
+
+        let header: &ShellLinkHeader = (&header_data[..]).load();
+        // unknown bits are kept, so the link can be written back the way it was
+        let link_flags = unsafe { LinkFlags::from_bits_unchecked(header.link_flags.get()) };
+
+        let file_attributes = header.file_attributes.get();
+        let creation_time = header.creation_time.get();
+        let access_time = header.access_time.get();
+        let write_time = header.write_time.get();
+        let file_size = header.file_size.get();
+        let icon_index = header.icon_index.get();
+        let show_command = header.show_command.get();
+        let hotkey = header.hotkey.get();
+
+        let mut offset = header_data.len();
+
+        let link_target_id_list = if link_flags.contains(LinkFlags::HAS_LINK_TARGET_ID_LIST) {
+            let data = Reader::new(src, 0, Section::IdList);
+            let size = data.u16_at(offset)? as usize;
+            let id_list = data.bytes(offset + 2, size)?;
+            offset += 2 + size;
+            Some(id_list.to_vec())
+        } else {
+            None
+        };
+
+        let link_info = if link_flags.contains(LinkFlags::HAS_LINK_INFO) {
+            let data = Reader::new(src, 0, Section::LinkInfo).from(offset)?;
+            let link_info = LinkInfo::load(data)?;
+            offset += data.u32_at(0)? as usize;
+            Some(link_info)
+        } else {
+            None
+        };
+
+        let data = Reader::new(src, 0, Section::StringData);
+        let unicode = link_flags.contains(LinkFlags::IS_UNICODE);
+        let mut load_string_data = |flag: LinkFlags| match link_flags.contains(flag) {
+            true => Self::load_string_data(data, &mut offset, unicode).map(Some),
+            false => Ok(None),
+        };
+
+        let name = load_string_data(LinkFlags::HAS_NAME)?;
+        let relative_path = load_string_data(LinkFlags::HAS_RELATIVE_PATH)?;
+        let working_dir = load_string_data(LinkFlags::HAS_WORKING_DIR)?;
+        let command_line_arguments = load_string_data(LinkFlags::HAS_ARGUMENTS)?;
+        let icon_location = load_string_data(LinkFlags::HAS_ICON_LOCATION)?;
+
+        let data = Reader::new(src, 0, Section::ExtraData);
+        let mut extra_data = vec![];
+        // the terminal block is sometimes missing
+        while offset < src.len() {
+            let size = data.u32_at(offset)? as usize;
+            match size {
+                0..=3 => break,
+                4..=7 => return Err(data.error(offset, Reason::InvalidSize(size))),
+                _ => {}
+            }
+
+            let signature = data.u32_at(offset + 4)?;
+            let block = data.sub(offset + 8, size - 8)?;
+            extra_data.push(ExtraData::load(signature, block)?);
+            offset += size;
+        }
+
+        Ok(ShellLink {
+            link_flags,
+            file_attributes,
+            creation_time,
+            access_time,
+            write_time,
+            file_size,
+            icon_index,
+            show_command,
+            hotkey,
+            link_target_id_list,
+            link_info,
+            name,
+            relative_path,
+            working_dir,
+            command_line_arguments,
+            icon_location,
+            extra_data,
+        })
+    }
+
+    fn store_string_data(data: &mut Vec<u8>, value: &str, unicode: bool) -> Result<(), Error> {
+        let (len, chars) = if unicode {
+            let chars = utf16_bytes(value);
+            (chars.len() / 2, chars)
+        } else {
+            let chars = encode_ansi(value);
+            (chars.len(), chars)
+        };
+
+        data.write_u16(size_u16(data, len, Section::StringData)?);
+        data.extend(chars);
+        Ok(())
+    }
+
+    /// Serialize the link, the inverse of `load`. The flags for which parts are there follow
+    /// the fields, the others are written as they are. Fails when a string or the id list is
+    /// too long for its size field
+    pub fn store(&self) -> Result<Vec<u8>, Error> {
+        let has_block = |signature| self.extra_data.iter().any(|b| b.signature() == signature);
+
+        let mut link_flags = self.link_flags;
+        let present = [
+            (
+                LinkFlags::HAS_LINK_TARGET_ID_LIST,
+                self.link_target_id_list.is_some(),
+            ),
+            (LinkFlags::HAS_LINK_INFO, self.link_info.is_some()),
+            (LinkFlags::HAS_NAME, self.name.is_some()),
+            (LinkFlags::HAS_RELATIVE_PATH, self.relative_path.is_some()),
+            (LinkFlags::HAS_WORKING_DIR, self.working_dir.is_some()),
+            (
+                LinkFlags::HAS_ARGUMENTS,
+                self.command_line_arguments.is_some(),
+            ),
+            (LinkFlags::HAS_ICON_LOCATION, self.icon_location.is_some()),
+            (LinkFlags::HAS_EXP_STRING, has_block(ENVIRONMENT_VARIABLES)),
+            (LinkFlags::HAS_DARWIN_ID, has_block(DARWIN)),
+            (LinkFlags::HAS_EXP_ICON, has_block(ICON_ENVIRONMENT)),
+        ];
+        for (flag, present) in present.iter() {
+            link_flags.set(*flag, *present);
+        }
+
+        let mut clsid = &LNK_CLSID.0[..];
+        let header = ShellLinkHeader {
+            header_size: u32le::new(0x4C),
+            lnk_clsid: clsid.load(),
+            link_flags: u32le::new(link_flags.bits()),
+            file_attributes: u32le::new(self.file_attributes),
+            creation_time: u64le::new(self.creation_time),
+            access_time: u64le::new(self.access_time),
+            write_time: u64le::new(self.write_time),
+            file_size: u32le::new(self.file_size),
+            icon_index: i32le::new(self.icon_index),
+            show_command: u32le::new(self.show_command),
+            hotkey: u16le::new(self.hotkey),
+            _reserved1: Default::default(),
+            _reserved2: Default::default(),
+            _reserved3: Default::default(),
+        };
+
+        let mut data = vec![];
+        data.store(header);
+
+        if let Some(id_list) = &self.link_target_id_list {
+            data.write_u16(size_u16(&data, id_list.len(), Section::IdList)?);
+            data.extend_from_slice(id_list);
+        }
+
+        if let Some(link_info) = &self.link_info {
+            link_info.store(&mut data);
+        }
+
+        let unicode = link_flags.contains(LinkFlags::IS_UNICODE);
+        let strings = [
+            &self.name,
+            &self.relative_path,
+            &self.working_dir,
+            &self.command_line_arguments,
+            &self.icon_location,
+        ];
+        for value in strings.iter().filter_map(|value| value.as_deref()) {
+            Self::store_string_data(&mut data, value, unicode)?;
+        }
+
+        for block in &self.extra_data {
+            block.store(&mut data);
+        }
+
+        // the terminal block
+        data.write_u32(0);
+        Ok(data)
+    }
+}
+
+// a size that has to fit the u16 written at the end of `data`
+fn size_u16(data: &[u8], size: usize, section: Section) -> Result<u16, Error> {
+    u16::try_from(size).map_err(|_| Error {
+        offset: data.len(),
+        section,
+        reason: Reason::InvalidSize(size),
+    })
+}
+
+flat_data!(ShellLinkHeader);
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub struct ShellLinkHeader {
+    pub header_size: u32le,
+    pub lnk_clsid: [u32le; 4],
+    pub link_flags: u32le,
+    pub file_attributes: u32le,
+    pub creation_time: u64le,
+    pub access_time: u64le,
+    pub write_time: u64le,
+    pub file_size: u32le,
+    pub icon_index: i32le,
+    pub show_command: u32le,
+    pub hotkey: u16le,
+    pub _reserved1: u16le,
+    pub _reserved2: u32le,
+    pub _reserved3: u32le,
+}
+
+bitflags! {
+    pub struct LinkFlags: u32 {
+        const HAS_LINK_TARGET_ID_LIST = 1 << 0;
+        const HAS_LINK_INFO = 1 << 1;
+        const HAS_NAME = 1 << 2;
+        const HAS_RELATIVE_PATH = 1 << 3;
+        const HAS_WORKING_DIR = 1 << 4;
+        const HAS_ARGUMENTS = 1 << 5;
+        const HAS_ICON_LOCATION = 1 << 6;
+        const IS_UNICODE = 1 << 7;
+        const FORCE_NO_LINK_INFO = 1 << 8;
+        const HAS_EXP_STRING = 1 << 9;
+        const RUN_IN_SEPARATE_PROCESS = 1 << 10;
+        // const UNUSED1 = 1 << 11;
+        const HAS_DARWIN_ID = 1 << 12;
+        const RUN_AS_USER = 1 << 13;
+        const HAS_EXP_ICON = 1 << 14;
+        const NO_PIDL_ALIAS = 1 << 15;
+        // const UNUSED2 = 1 << 16;
+        const RUN_WITH_SHIM_LAYER = 1 << 17;
+        const FORCE_NO_LINK_TRACK = 1 << 18;
+        const ENABLE_TARGET_METADATA = 1 << 19;
+        const DISABLE_LINK_PATH_TRACKING = 1 << 20;
+        const DISABLE_KNOWN_FOLDER_TRACKING = 1 << 21;
+        const DISABLE_KNOWN_FOLDER_ALIAS = 1 << 22;
+        const ALLOW_LINK_TO_LINK = 1 << 23;
+        const UNALIAS_ON_SAVE = 1 << 24;
+        const PREFER_ENVIRONMENT_PATH = 1 << 25;
+        const KEEP_LOCAL_ID_LIST_FOR_UNC_TARGET = 1 << 26;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn fixture(name: &str) -> ShellLink {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(name);
+        ShellLink::load(&std::fs::read(path).unwrap()).unwrap()
+    }
+
+    fn fixtures(dir: &str) -> Vec<(String, Vec<u8>)> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(dir);
+        let mut files: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "lnk"))
+            .map(|path| {
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                (name, std::fs::read(&path).unwrap())
+            })
+            .collect();
+
+        files.sort();
+        files
+    }
+
+    // everything a caller might do with a link that loaded
+    fn exercise(src: &[u8]) -> Result<(), Error> {
+        let lnk = ShellLink::load(src)?;
+        if let Some(id_list) = lnk.id_list() {
+            id_list?.location();
+        }
+        if let Some(link_info) = &lnk.link_info {
+            link_info.path();
+        }
+        lnk.app_user_model_id();
+        Ok(())
+    }
+
+    #[test]
+    fn corrupt_links() {
+        for (name, src) in fixtures("fixtures/corrupt") {
+            if let Ok(()) = exercise(&src) {
+                panic!("{} loaded", name);
+            }
+        }
+
+        // truncate and break each byte of the good links, none of it may panic
+        for (_, src) in fixtures("fixtures") {
+            for len in 0..src.len() {
+                let _ = exercise(&src[..len]);
+            }
+
+            for i in 0..src.len() {
+                let mut src = src.clone();
+                src[i] ^= 0xFF;
+                let _ = exercise(&src);
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_flags() {
+        let mut src =
+            std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/celeste.lnk"))
+                .unwrap();
+        src[0x17] |= 0x80;
+
+        let lnk = ShellLink::load(&src).unwrap();
+        assert_eq!(lnk.link_flags.bits() >> 24, 0x80);
+        assert_eq!(lnk.command_line_arguments.as_deref(), Some("-windowed"));
+
+        let error = ShellLink::load(&src[..0x60]).err().unwrap();
+        assert_eq!(error.section, Section::LinkInfo);
+        assert_eq!(error.reason, Reason::UnexpectedEnd);
+    }
+
+    #[test]
+    fn round_trip() {
+        for (name, src) in fixtures("fixtures") {
+            let lnk = ShellLink::load(&src).unwrap();
+            assert!(lnk.store().unwrap() == src, "{} changed", name);
+        }
+
+        // unknown flags are written back too
+        let mut src =
+            std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/celeste.lnk"))
+                .unwrap();
+        src[0x17] |= 0x80;
+        assert!(ShellLink::load(&src).unwrap().store().unwrap() == src);
+    }
+
+    #[test]
+    fn new_links() {
+        let mut lnk = ShellLink::new(r"C:\Windows\explorer.exe");
+        lnk.command_line_arguments =
+            Some(r"shell:AppsFolder\Microsoft.WindowsCalculator_8wekyb3d8bbwe!App".into());
+        lnk.set_app_user_model_id("Microsoft.WindowsCalculator_8wekyb3d8bbwe!App");
+
+        let lnk = ShellLink::load(&lnk.store().unwrap()).unwrap();
+        let link_info = lnk.link_info.as_ref().unwrap();
+        assert_eq!(
+            link_info.path().as_deref(),
+            Some(r"C:\Windows\explorer.exe")
+        );
+        assert_eq!(link_info.local_base_path_unicode, None);
+        assert!(lnk.link_flags.contains(LinkFlags::HAS_ARGUMENTS));
+        assert_eq!(
+            lnk.app_user_model_id(),
+            Some("Microsoft.WindowsCalculator_8wekyb3d8bbwe!App")
+        );
+
+        let lnk = ShellLink::load(
+            &ShellLink::new("D:\\Games\\\u{14c}kami HD\\okami.exe")
+                .store()
+                .unwrap(),
+        );
+        let link_info = lnk.unwrap().link_info.unwrap();
+        assert_eq!(
+            link_info.local_base_path.as_deref(),
+            Some(r"D:\Games\?kami HD\okami.exe")
+        );
+        assert_eq!(
+            link_info.path().as_deref(),
+            Some("D:\\Games\\\u{14c}kami HD\\okami.exe")
+        );
+
+        let lnk = ShellLink::load(
+            &ShellLink::new(r"\\NAS\games\Celeste\Celeste.exe")
+                .store()
+                .unwrap(),
+        );
+        let link_info = lnk.unwrap().link_info.unwrap();
+        let link = link_info.common_network_relative_link.as_ref().unwrap();
+        assert_eq!(link.net_name, r"\\NAS\games");
+        assert_eq!(link_info.common_path_suffix, r"Celeste\Celeste.exe");
+        assert_eq!(
+            link_info.path().as_deref(),
+            Some(r"\\NAS\games\Celeste\Celeste.exe")
+        );
+    }
+
+    #[test]
+    fn too_long_to_store() {
+        let mut lnk = ShellLink::new(r"C:\Windows\explorer.exe");
+        lnk.command_line_arguments = Some("a".repeat(0x10000));
+        let error = lnk.store().unwrap_err();
+        assert_eq!(error.section, Section::StringData);
+        assert_eq!(error.reason, Reason::InvalidSize(0x10000));
+
+        // the longest string that fits, not cut down to its length modulo 0x10000
+        lnk.command_line_arguments = Some("\u{14c}".repeat(0xFFFF));
+        let lnk = ShellLink::load(&lnk.store().unwrap()).unwrap();
+        assert_eq!(lnk.command_line_arguments.unwrap().chars().count(), 0xFFFF);
+
+        let mut lnk = ShellLink::new(r"C:\Windows\explorer.exe");
+        lnk.link_target_id_list = Some(vec![0; 0x10000]);
+        assert_eq!(lnk.store().unwrap_err().section, Section::IdList);
+    }
+
+    #[test]
+    fn ansi_code_page() {
+        assert_eq!(decode_ansi(b"Handb\xfccher \x80 5"), "Handbücher € 5");
+    }
+
+    #[test]
+    fn expand_environment() {
+        let env: Environment = vec![("SystemRoot", r"C:\Windows"), ("empty", "")]
+            .into_iter()
+            .collect();
+
+        assert_eq!(env.expand(r"%systemroot%\system32"), r"C:\Windows\system32");
+        assert_eq!(env.expand("a%EMPTY%b"), "ab");
+        assert_eq!(
+            env.expand("%missing%\\%SystemRoot%"),
+            r"%missing%\C:\Windows"
+        );
+        assert_eq!(env.expand("100%"), "100%");
+        assert_eq!(env.expand("%%SystemRoot%"), r"%%SystemRoot%");
+        assert_eq!(env.expand("50% of %SystemRoot"), "50% of %SystemRoot");
+
+        // longer than the buffers windows is usually given
+        let long = "x".repeat(5000);
+        let env: Environment = vec![("LONG", &long)].into_iter().collect();
+        assert_eq!(env.expand("%LONG%%LONG%").len(), 10000);
+
+        // names keep the case they were first set with
+        let mut env: Environment = vec![("Path", r"C:\Windows")].into_iter().collect();
+        env.set("PATH", r"C:\Tools");
+        env.set("temp", r"C:\Temp");
+        env.remove("TEMP");
+
+        let vars: Vec<_> = env.iter().collect();
+        assert_eq!(vars, vec![("Path", r"C:\Tools")]);
+    }
+
+    #[test]
+    fn hotkeys() {
+        let mut lnk = fixture("notepad.lnk");
+        assert_eq!(lnk.hotkey_name(), None);
+
+        lnk.hotkey = 0x064E;
+        assert_eq!(lnk.hotkey_name().as_deref(), Some("Ctrl + Alt + N"));
+        lnk.hotkey = 0x0173;
+        assert_eq!(lnk.hotkey_name().as_deref(), Some("Shift + F4"));
+        lnk.hotkey = 0x0790;
+        assert_eq!(
+            lnk.hotkey_name().as_deref(),
+            Some("Ctrl + Alt + Shift + Num Lock")
+        );
+
+        assert_eq!(fixture("celeste.lnk").show_command, SW_SHOWMAXIMIZED);
+    }
+
+    #[test]
+    fn target_with_environment() {
+        let env: Environment = vec![("windir", r"D:\Win"), ("SystemRoot", r"D:\Win")]
+            .into_iter()
+            .collect();
+
+        let mut lnk = fixture("notepad.lnk");
+        let local = Location::Path(r"C:\Windows\System32\notepad.exe".into());
+        assert_eq!(lnk.target(&env), Some(local));
+
+        lnk.link_info = None;
+        let expanded = Location::Path(r"D:\Win\system32\notepad.exe".into());
+        assert_eq!(lnk.target(&env), Some(expanded));
+        assert_eq!(
+            lnk.icon_path(&env).as_deref(),
+            Some(r"D:\Win\system32\notepad.exe")
+        );
+
+        let lnk = fixture("powershell.lnk");
+        assert_eq!(
+            lnk.icon_path(&env).as_deref(),
+            Some(r"D:\Win\system32\WindowsPowerShell\v1.0\powershell.exe")
+        );
+        assert_eq!(fixture("control.lnk").icon_path(&env), None);
+    }
+
+    #[test]
+    fn id_list() {
+        let lnk = fixture("notepad.lnk");
+        let id_list = lnk.id_list().unwrap().unwrap();
+
+        assert_eq!(id_list.0[0], ShellItem::Folder(folders::MY_COMPUTER));
+        assert_eq!(id_list.0[1], ShellItem::Drive(r"C:\".to_owned()));
+        match &id_list.0[4] {
+            ShellItem::File(entry) => {
+                assert!(!entry.is_directory);
+                assert_eq!(entry.name(), "notepad.exe");
+                assert_eq!(entry.file_size, 201216);
+                assert_eq!(entry.created, Some(entry.modified));
+            }
+            item => panic!("expected a file entry, got {:?}", item),
+        }
+        assert_eq!(
+            id_list.location(),
+            Some(Location::Path(
+                r"C:\Windows\System32\notepad.exe".to_owned()
+            ))
+        );
+
+        let saves = fixture("saves.lnk").id_list().unwrap().unwrap();
+        assert_eq!(
+            saves.location(),
+            Some(Location::Virtual {
+                folder: folders::DOCUMENTS,
+                items: vec!["My Games".to_owned(), "Celeste Saves".to_owned()],
+            })
+        );
+
+        let control = fixture("control.lnk").id_list().unwrap().unwrap();
+        assert_eq!(
+            control.location(),
+            Some(Location::Virtual {
+                folder: folders::CONTROL_PANEL,
+                items: vec![],
+            })
+        );
+
+        let wiki = fixture("wiki.lnk").id_list().unwrap().unwrap();
+        assert_eq!(
+            wiki.location(),
+            Some(Location::Uri(
+                "https://celestegame.fandom.com/wiki/Celeste".to_owned()
+            ))
+        );
+        assert_eq!(
+            folders::MY_COMPUTER.to_string(),
+            "{20D04FE0-3AEA-1069-A2D8-08002B30309D}"
+        );
+    }
+
+    #[test]
+    fn extra_data() {
+        let lnk = fixture("notepad.lnk");
+        let blocks = &lnk.extra_data;
+
+        assert_eq!(
+            lnk.environment_variable_data(),
+            Some(r"%windir%\system32\notepad.exe")
+        );
+        assert_eq!(
+            blocks[1],
+            ExtraData::SpecialFolder(SpecialFolder {
+                folder_id: 0x25,
+                offset: item_offset(&lnk, 3),
+            })
+        );
+        match &blocks[2] {
+            ExtraData::KnownFolder(known) => assert_eq!(
+                known.folder.to_string(),
+                "{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}"
+            ),
+            block => panic!("expected a known folder, got {:?}", block),
+        }
+        match &blocks[4] {
+            ExtraData::Tracker(tracker) => {
+                assert_eq!(tracker.machine_id, "desktop-4f2k9");
+                assert_eq!(tracker.droid, tracker.droid_birth);
+            }
+            block => panic!("expected a tracker, got {:?}", block),
+        }
+        assert_eq!(lnk.app_user_model_id(), None);
+
+        let lnk = fixture("powershell.lnk");
+        match &lnk.extra_data[0] {
+            ExtraData::Console(console) => {
+                assert_eq!(console.face_name, "Consolas");
+                assert_eq!(console.screen_buffer_size, (120, 9001));
+                assert!(console.quick_edit);
+                assert_eq!(console.color_table[15], 0xF2F2F2);
+            }
+            block => panic!("expected a console, got {:?}", block),
+        }
+        assert_eq!(
+            lnk.icon_environment_data(),
+            Some(r"%SystemRoot%\system32\WindowsPowerShell\v1.0\powershell.exe")
+        );
+        assert_eq!(
+            lnk.app_user_model_id(),
+            Some("Microsoft.Windows.PowerShell")
+        );
+
+        let lnk = fixture("excel.lnk");
+        assert!(lnk.link_flags.contains(LinkFlags::HAS_DARWIN_ID));
+        match &lnk.extra_data[0] {
+            ExtraData::Darwin(darwin) => {
+                assert!(darwin
+                    .value()
+                    .starts_with("w_1^VX!!!!!!!!!MKKSkEXCELFiles>"))
+            }
+            block => panic!("expected a darwin id, got {:?}", block),
+        }
+    }
+
+    // offset of an item within the id list, as the folder blocks refer to them
+    fn item_offset(lnk: &ShellLink, item: usize) -> u32 {
+        let data = lnk.link_target_id_list.as_deref().unwrap();
+        let mut offset = 0;
+        for _ in 0..item {
+            offset += u16::from_le_bytes([data[offset], data[offset + 1]]) as usize;
+        }
+        offset as u32
+    }
+
+    #[test]
+    fn local_link_info() {
+        let lnk = fixture("notepad.lnk");
+        let info = lnk.link_info.as_ref().unwrap();
+        let volume = info.volume_id.as_ref().unwrap();
+
+        assert_eq!(volume.drive_type, DriveType::Fixed);
+        assert_eq!(volume.serial_number, 0x4C2E_91A8);
+        assert_eq!(volume.label, "");
+        assert_eq!(info.common_network_relative_link, None);
+        assert_eq!(
+            info.path().as_deref(),
+            Some(r"C:\Windows\System32\notepad.exe")
+        );
+        assert_eq!(lnk.working_dir.as_deref(), Some(r"%windir%\system32"));
+    }
+
+    #[test]
+    fn network_link_info() {
+        let lnk = fixture("celeste.lnk");
+        let info = lnk.link_info.as_ref().unwrap();
+        let network = info.common_network_relative_link.as_ref().unwrap();
+
+        assert_eq!(info.volume_id, None);
+        assert_eq!(network.net_name, r"\\NAS\games");
+        assert_eq!(network.device_name.as_deref(), Some("Z:"));
+        assert_eq!(network.provider_type, Some(0x20000));
+        assert_eq!(
+            info.path().as_deref(),
+            Some(r"\\NAS\games\Celeste\Celeste.exe")
+        );
+        assert_eq!(lnk.command_line_arguments.as_deref(), Some("-windowed"));
+        assert_eq!(lnk.show_command, 3);
+    }
+
+    #[test]
+    fn unicode_link_info() {
+        let lnk = fixture("okami.lnk");
+        let info = lnk.link_info.as_ref().unwrap();
+        let volume = info.volume_id.as_ref().unwrap();
+
+        assert!(volume.unicode_label);
+        assert_eq!(volume.label, "Spiele");
+        assert_eq!(
+            info.local_base_path.as_deref(),
+            Some(r"D:\Games\?kami HD\okami.exe")
+        );
+        assert_eq!(info.path().as_deref(), Some(r"D:\Games\Ōkami HD\okami.exe"));
+        assert_eq!(lnk.name.as_deref(), Some("Ōkami HD"));
+        assert_eq!(lnk.environment_variable_data(), None);
+        assert_eq!(lnk.link_target_id_list, None);
+    }
+
+    #[test]
+    fn ansi_link_info() {
+        let lnk = fixture("readme.lnk");
+        let info = lnk.link_info.as_ref().unwrap();
+        let volume = info.volume_id.as_ref().unwrap();
+        let network = info.common_network_relative_link.as_ref().unwrap();
+
+        assert!(!lnk.link_flags.contains(LinkFlags::IS_UNICODE));
+        assert_eq!(volume.drive_type, DriveType::Remote);
+        assert_eq!(volume.label, "Daten");
+        assert_eq!(
+            network.net_name_unicode.as_deref(),
+            Some(r"\\FILESERVER\Daten")
+        );
+        assert_eq!(network.device_name_unicode.as_deref(), Some("Z:"));
+        assert_eq!(info.path().as_deref(), Some(r"Z:\Handbücher\Liesmich.txt"));
+        assert_eq!(lnk.relative_path.as_deref(), Some(r".\Liesmich.txt"));
+    }
+}
@@ -0,0 +1,371 @@
+use crate::{decode_ansi, encode_ansi, reader::Reader, writer::Writer, Error};
+
+pub const VOLUME_ID_AND_LOCAL_BASE_PATH: u32 = 1 << 0;
+pub const COMMON_NETWORK_RELATIVE_LINK_AND_PATH_SUFFIX: u32 = 1 << 1;
+
+/// Where the target was found when the link was created, so it can be found again if
+/// the id list no longer resolves
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkInfo {
+    pub flags: u32,
+    pub volume_id: Option<VolumeId>,
+    pub local_base_path: Option<String>,
+    pub common_network_relative_link: Option<CommonNetworkRelativeLink>,
+    pub common_path_suffix: String,
+    // only written when the paths don't fit in the ansi code page
+    pub local_base_path_unicode: Option<String>,
+    pub common_path_suffix_unicode: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriveType {
+    Unknown,
+    NoRootDir,
+    Removable,
+    Fixed,
+    Remote,
+    CdRom,
+    RamDisk,
+    Other(u32),
+}
+
+impl DriveType {
+    pub fn from_raw(raw: u32) -> DriveType {
+        match raw {
+            0 => DriveType::Unknown,
+            1 => DriveType::NoRootDir,
+            2 => DriveType::Removable,
+            3 => DriveType::Fixed,
+            4 => DriveType::Remote,
+            5 => DriveType::CdRom,
+            6 => DriveType::RamDisk,
+            raw => DriveType::Other(raw),
+        }
+    }
+
+    pub fn to_raw(self) -> u32 {
+        match self {
+            DriveType::Unknown => 0,
+            DriveType::NoRootDir => 1,
+            DriveType::Removable => 2,
+            DriveType::Fixed => 3,
+            DriveType::Remote => 4,
+            DriveType::CdRom => 5,
+            DriveType::RamDisk => 6,
+            DriveType::Other(raw) => raw,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VolumeId {
+    pub drive_type: DriveType,
+    pub serial_number: u32,
+    pub label: String,
+    // the label was stored as utf-16
+    pub unicode_label: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommonNetworkRelativeLink {
+    pub net_name: String,
+    pub device_name: Option<String>,
+    // WNNC_NET_* of the network provider
+    pub provider_type: Option<u32>,
+    pub net_name_unicode: Option<String>,
+    pub device_name_unicode: Option<String>,
+}
+
+const VALID_DEVICE: u32 = 1 << 0;
+const VALID_NET_TYPE: u32 = 1 << 1;
+
+impl VolumeId {
+    fn load(data: Reader) -> Result<VolumeId, Error> {
+        let data = data.sized(0x10)?;
+
+        let drive_type = DriveType::from_raw(data.u32_at(4)?);
+        let serial_number = data.u32_at(8)?;
+        let label_offset = data.u32_at(12)? as usize;
+
+        // an offset of 0x14 means the ansi label is unused and a unicode offset follows
+        let (label, unicode_label) = match label_offset {
+            0x14 => (data.unicode_at(data.u32_at(16)? as usize)?, true),
+            offset => (data.ansi_at(offset)?, false),
+        };
+
+        Ok(VolumeId {
+            drive_type,
+            serial_number,
+            label,
+            unicode_label,
+        })
+    }
+
+    fn store(&self, out: &mut Vec<u8>) {
+        let mut data = vec![];
+        data.write_u32(0);
+        data.write_u32(self.drive_type.to_raw());
+        data.write_u32(self.serial_number);
+
+        if self.unicode_label {
+            data.write_u32(0x14);
+            data.write_u32(0x14);
+            data.write_unicode(&self.label);
+        } else {
+            data.write_u32(0x10);
+            data.write_ansi(&self.label);
+        }
+
+        data.write_size();
+        out.extend(data);
+    }
+}
+
+impl CommonNetworkRelativeLink {
+    fn load(data: Reader) -> Result<CommonNetworkRelativeLink, Error> {
+        let data = data.sized(0x14)?;
+
+        let flags = data.u32_at(4)?;
+        let net_name_offset = data.u32_at(8)? as usize;
+        let device_name_offset = data.u32_at(12)? as usize;
+        let provider_type = data.u32_at(16)?;
+
+        let net_name = data.ansi_at(net_name_offset)?;
+        let device_name = match flags & VALID_DEVICE {
+            0 => None,
+            _ => Some(data.ansi_at(device_name_offset)?),
+        };
+
+        // the unicode offsets are only there when the ansi strings start after them
+        let (net_name_unicode, device_name_unicode) = if net_name_offset > 0x14 {
+            let net_name = data.unicode_at(data.u32_at(20)? as usize)?;
+            let device_name = match flags & VALID_DEVICE {
+                0 => None,
+                _ => Some(data.unicode_at(data.u32_at(24)? as usize)?),
+            };
+
+            (Some(net_name), device_name)
+        } else {
+            (None, None)
+        };
+
+        Ok(CommonNetworkRelativeLink {
+            net_name,
+            device_name,
+            provider_type: Some(provider_type).filter(|_| flags & VALID_NET_TYPE != 0),
+            net_name_unicode,
+            device_name_unicode,
+        })
+    }
+
+    fn store(&self, out: &mut Vec<u8>) {
+        let mut flags = 0;
+        if self.device_name.is_some() {
+            flags |= VALID_DEVICE;
+        }
+        if self.provider_type.is_some() {
+            flags |= VALID_NET_TYPE;
+        }
+
+        let mut data = vec![];
+        data.write_u32(0);
+        data.write_u32(flags);
+        data.write_u32(0);
+        data.write_u32(0);
+        data.write_u32(self.provider_type.unwrap_or(0));
+        if self.net_name_unicode.is_some() {
+            data.write_u32(0);
+            data.write_u32(0);
+        }
+
+        data.mark_offset(8);
+        data.write_ansi(&self.net_name);
+        if let Some(device_name) = &self.device_name {
+            data.mark_offset(12);
+            data.write_ansi(device_name);
+        }
+
+        if let Some(net_name) = &self.net_name_unicode {
+            data.mark_offset(20);
+            data.write_unicode(net_name);
+            if let Some(device_name) = &self.device_name {
+                data.mark_offset(24);
+                data.write_unicode(self.device_name_unicode.as_ref().unwrap_or(device_name));
+            }
+        }
+
+        data.write_size();
+        out.extend(data);
+    }
+}
+
+impl LinkInfo {
+    pub(crate) fn load(data: Reader) -> Result<LinkInfo, Error> {
+        let data = data.sized(0x1C)?;
+        let header_size = data.u32_at(4)? as usize;
+        let flags = data.u32_at(8)?;
+        let offset_at = |at| Ok::<_, Error>(data.u32_at(at)? as usize);
+
+        let (volume_id, local_base_path) = match flags & VOLUME_ID_AND_LOCAL_BASE_PATH {
+            0 => (None, None),
+            _ => {
+                let volume_id = VolumeId::load(data.from(offset_at(12)?)?)?;
+                let base = data.ansi_at(offset_at(16)?)?;
+                (Some(volume_id), Some(base))
+            }
+        };
+
+        let common_network_relative_link =
+            match flags & COMMON_NETWORK_RELATIVE_LINK_AND_PATH_SUFFIX {
+                0 => None,
+                _ => Some(CommonNetworkRelativeLink::load(data.from(offset_at(20)?)?)?),
+            };
+
+        let common_path_suffix = data.ansi_at(offset_at(24)?)?;
+
+        // headers of 0x24 bytes or more add the offsets of unicode copies of the paths
+        let (local_base_path_unicode, common_path_suffix_unicode) = if header_size >= 0x24 {
+            let base = match flags & VOLUME_ID_AND_LOCAL_BASE_PATH {
+                0 => None,
+                _ => Some(data.unicode_at(offset_at(0x1C)?)?),
+            };
+            let suffix = data.unicode_at(offset_at(0x20)?)?;
+
+            (base, Some(suffix))
+        } else {
+            (None, None)
+        };
+
+        Ok(LinkInfo {
+            flags,
+            volume_id,
+            local_base_path,
+            common_network_relative_link,
+            common_path_suffix,
+            local_base_path_unicode,
+            common_path_suffix_unicode,
+        })
+    }
+
+    pub(crate) fn store(&self, out: &mut Vec<u8>) {
+        let unicode =
+            self.local_base_path_unicode.is_some() || self.common_path_suffix_unicode.is_some();
+        let header_size = if unicode { 0x24 } else { 0x1C };
+
+        let mut flags = self.flags
+            & !(VOLUME_ID_AND_LOCAL_BASE_PATH | COMMON_NETWORK_RELATIVE_LINK_AND_PATH_SUFFIX);
+        if self.volume_id.is_some() {
+            flags |= VOLUME_ID_AND_LOCAL_BASE_PATH;
+        }
+        if self.common_network_relative_link.is_some() {
+            flags |= COMMON_NETWORK_RELATIVE_LINK_AND_PATH_SUFFIX;
+        }
+
+        let mut data = vec![];
+        data.write_u32(0);
+        data.write_u32(header_size);
+        data.write_u32(flags);
+        data.resize(header_size as usize, 0);
+
+        let base = self.local_base_path.as_deref().unwrap_or("");
+        if let Some(volume_id) = &self.volume_id {
+            data.mark_offset(12);
+            volume_id.store(&mut data);
+            data.mark_offset(16);
+            data.write_ansi(base);
+        }
+
+        if let Some(link) = &self.common_network_relative_link {
+            data.mark_offset(20);
+            link.store(&mut data);
+        }
+
+        data.mark_offset(24);
+        data.write_ansi(&self.common_path_suffix);
+
+        if unicode {
+            if self.volume_id.is_some() {
+                data.mark_offset(0x1C);
+                data.write_unicode(self.local_base_path_unicode.as_deref().unwrap_or(base));
+            }
+
+            let suffix = self.common_path_suffix_unicode.as_ref();
+            data.mark_offset(0x20);
+            data.write_unicode(suffix.unwrap_or(&self.common_path_suffix));
+        }
+
+        data.write_size();
+        out.extend(data);
+    }
+
+    /// Link info for a local path like `C:\Games\game.exe`, or one on a share like
+    /// `\\server\share\game.exe`. The volume isn't looked up, the path is enough to find it
+    pub fn from_path(path: &str) -> LinkInfo {
+        // paths outside the ansi code page get unicode copies
+        let unicode = decode_ansi(&encode_ansi(path)) != path;
+        let copy = |value: &str| Some(value.to_owned()).filter(|_| unicode);
+
+        if let Some(unc) = path.strip_prefix(r"\\") {
+            // the share is \\server\share, the rest is the suffix
+            let mut parts = unc.splitn(3, '\\');
+            let server = parts.next().unwrap_or("");
+            let share = parts.next().unwrap_or("");
+            let suffix = parts.next().unwrap_or("");
+            let net_name = format!(r"\\{}\{}", server, share);
+
+            return LinkInfo {
+                flags: COMMON_NETWORK_RELATIVE_LINK_AND_PATH_SUFFIX,
+                volume_id: None,
+                local_base_path: None,
+                common_network_relative_link: Some(CommonNetworkRelativeLink {
+                    net_name_unicode: copy(&net_name),
+                    net_name,
+                    device_name: None,
+                    provider_type: None,
+                    device_name_unicode: None,
+                }),
+                common_path_suffix: suffix.to_owned(),
+                local_base_path_unicode: None,
+                common_path_suffix_unicode: copy(suffix),
+            };
+        }
+
+        LinkInfo {
+            flags: VOLUME_ID_AND_LOCAL_BASE_PATH,
+            volume_id: Some(VolumeId {
+                drive_type: DriveType::Fixed,
+                serial_number: 0,
+                label: String::new(),
+                unicode_label: false,
+            }),
+            local_base_path: Some(path.to_owned()),
+            common_network_relative_link: None,
+            common_path_suffix: String::new(),
+            local_base_path_unicode: copy(path),
+            common_path_suffix_unicode: copy(""),
+        }
+    }
+
+    /// The full path of the target, local if there is one, otherwise on the network share
+    pub fn path(&self) -> Option<String> {
+        let suffix = self
+            .common_path_suffix_unicode
+            .as_deref()
+            .unwrap_or(&self.common_path_suffix);
+
+        let base = self.local_base_path_unicode.as_deref();
+        let base = base.or(self.local_base_path.as_deref());
+        let base = base.or_else(|| {
+            let link = self.common_network_relative_link.as_ref()?;
+            Some(link.net_name_unicode.as_deref().unwrap_or(&link.net_name))
+        })?;
+
+        if suffix.is_empty() {
+            Some(base.to_owned())
+        } else if base.ends_with('\\') {
+            Some(format!("{}{}", base, suffix))
+        } else {
+            Some(format!("{}\\{}", base, suffix))
+        }
+    }
+}
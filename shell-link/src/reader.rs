@@ -0,0 +1,123 @@
+use crate::{decode_ansi, Error, Guid, Reason, Section};
+
+/// Bounds checked reads from a part of the file, errors point at the offset in the whole file
+#[derive(Clone, Copy)]
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+    section: Section,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8], offset: usize, section: Section) -> Reader<'a> {
+        Reader {
+            data,
+            offset,
+            section,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn error(&self, at: usize, reason: Reason) -> Error {
+        Error {
+            offset: self.offset + at,
+            section: self.section,
+            reason,
+        }
+    }
+
+    pub fn bytes(&self, at: usize, len: usize) -> Result<&'a [u8], Error> {
+        match at.checked_add(len) {
+            Some(end) if end <= self.data.len() => Ok(&self.data[at..end]),
+            _ => Err(self.error(at.min(self.data.len()), Reason::UnexpectedEnd)),
+        }
+    }
+
+    /// `len` bytes at `at`, as a reader of their own
+    pub fn sub(&self, at: usize, len: usize) -> Result<Reader<'a>, Error> {
+        let data = self.bytes(at, len)?;
+        Ok(Reader::new(data, self.offset + at, self.section))
+    }
+
+    /// A structure that starts with its u32 size, which has to cover at least `min` bytes
+    pub fn sized(&self, min: usize) -> Result<Reader<'a>, Error> {
+        let size = self.u32_at(0)? as usize;
+        if size < min {
+            return Err(self.error(0, Reason::InvalidSize(size)));
+        }
+
+        self.sub(0, size)
+    }
+
+    /// Everything from an offset stored in the structure
+    pub fn from(&self, at: usize) -> Result<Reader<'a>, Error> {
+        if at > self.data.len() {
+            return Err(self.error(0, Reason::InvalidOffset(at)));
+        }
+
+        Ok(Reader::new(
+            &self.data[at..],
+            self.offset + at,
+            self.section,
+        ))
+    }
+
+    pub fn u8_at(&self, at: usize) -> Result<u8, Error> {
+        Ok(self.bytes(at, 1)?[0])
+    }
+
+    pub fn u16_at(&self, at: usize) -> Result<u16, Error> {
+        let raw = self.bytes(at, 2)?;
+        Ok(u16::from_le_bytes([raw[0], raw[1]]))
+    }
+
+    pub fn u32_at(&self, at: usize) -> Result<u32, Error> {
+        let mut raw = [0; 4];
+        raw.copy_from_slice(self.bytes(at, 4)?);
+        Ok(u32::from_le_bytes(raw))
+    }
+
+    pub fn u64_at(&self, at: usize) -> Result<u64, Error> {
+        let mut raw = [0; 8];
+        raw.copy_from_slice(self.bytes(at, 8)?);
+        Ok(u64::from_le_bytes(raw))
+    }
+
+    pub fn guid_at(&self, at: usize) -> Result<Guid, Error> {
+        Ok(Guid::from_slice(self.bytes(at, 16)?))
+    }
+
+    /// A nul terminated string in the ansi code page
+    pub fn ansi_at(&self, at: usize) -> Result<String, Error> {
+        let data = self.bytes(at, 0).map(|_| &self.data[at..])?;
+        match data.iter().position(|b| *b == 0) {
+            Some(len) => Ok(decode_ansi(&data[..len])),
+            None => Err(self.error(at, Reason::UnterminatedString)),
+        }
+    }
+
+    /// A nul terminated utf-16 string
+    pub fn unicode_at(&self, at: usize) -> Result<String, Error> {
+        let mut len = 0;
+        while self.u16_at(at + len * 2)? != 0 {
+            len += 1;
+        }
+
+        self.utf16_at(at, len)
+    }
+
+    /// Exactly `len` utf-16 code units, less anything after a nul
+    pub fn utf16_at(&self, at: usize, len: usize) -> Result<String, Error> {
+        let raw = self.bytes(at, len.saturating_mul(2))?;
+        let utf16: Vec<u16> = raw
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .take_while(|c| *c != 0)
+            .collect();
+
+        String::from_utf16(&utf16).map_err(|_| self.error(at, Reason::InvalidUtf16))
+    }
+}
@@ -0,0 +1,68 @@
+use crate::{encode_ansi, Guid};
+
+/// Little endian writes, the other side of `Reader`. Offsets are relative to the start of
+/// the buffer, so each structure with its own offsets is written to a buffer of its own
+pub(crate) trait Writer {
+    fn write_u16(&mut self, value: u16);
+    fn write_u32(&mut self, value: u32);
+    fn write_guid(&mut self, value: Guid);
+
+    /// Nul terminated, in the ansi code page
+    fn write_ansi(&mut self, value: &str);
+    /// Nul terminated utf-16
+    fn write_unicode(&mut self, value: &str);
+    /// Cut or padded with nuls to `len` bytes
+    fn write_fixed(&mut self, value: &[u8], len: usize);
+
+    fn set_u32(&mut self, at: usize, value: u32);
+    /// Point the offset at `at` to the end of the buffer, where the next write goes
+    fn mark_offset(&mut self, at: usize);
+    /// Fill in the size at the start of the buffer
+    fn write_size(&mut self);
+}
+
+pub(crate) fn utf16_bytes(value: &str) -> Vec<u8> {
+    value.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+
+impl Writer for Vec<u8> {
+    fn write_u16(&mut self, value: u16) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn write_guid(&mut self, value: Guid) {
+        self.extend_from_slice(&value.0);
+    }
+
+    fn write_ansi(&mut self, value: &str) {
+        self.extend(encode_ansi(value));
+        self.push(0);
+    }
+
+    fn write_unicode(&mut self, value: &str) {
+        self.extend(utf16_bytes(value));
+        self.write_u16(0);
+    }
+
+    fn write_fixed(&mut self, value: &[u8], len: usize) {
+        let value = &value[..value.len().min(len)];
+        self.extend_from_slice(value);
+        self.resize(self.len() + len - value.len(), 0);
+    }
+
+    fn set_u32(&mut self, at: usize, value: u32) {
+        self[at..at + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn mark_offset(&mut self, at: usize) {
+        self.set_u32(at, self.len() as u32);
+    }
+
+    fn write_size(&mut self) {
+        self.set_u32(0, self.len() as u32);
+    }
+}
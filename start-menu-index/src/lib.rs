@@ -0,0 +1,377 @@
+use std::path::{Path, PathBuf};
+
+use shell_link::ShellLink;
+
+mod read_dir_recursive;
+pub use read_dir_recursive::*;
+
+pub mod url;
+pub use url::InternetShortcut;
+
+/// Which entries to list, by the extension of what they point to and by patterns for their
+/// relative path or display name
+#[derive(Debug, Clone, Default)]
+pub struct EntryFilter {
+    // empty allows every extension
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub patterns: Vec<String>,
+}
+
+impl EntryFilter {
+    // targets without an extension are folders, which open in explorer
+    pub fn allows_target(&self, target: &Path) -> bool {
+        let ext = match target.extension().and_then(|x| x.to_str()) {
+            Some(ext) => ext,
+            None => return false,
+        };
+
+        let listed = |list: &[String]| list.iter().any(|e| e.eq_ignore_ascii_case(ext));
+        (self.include.is_empty() || listed(&self.include)) && !listed(&self.exclude)
+    }
+
+    pub fn excludes(&self, value: &str) -> bool {
+        self.patterns
+            .iter()
+            .any(|pattern| glob_match(pattern, value))
+    }
+
+    /// Patterns are written with `/`, whatever separator the path has
+    pub fn excludes_path(&self, relative: &Path) -> bool {
+        self.excludes(&relative.to_string_lossy().replace('\\', "/"))
+    }
+}
+
+/// Match `value` against a pattern where `*` is any run of characters and `?` is any one,
+/// ignoring case
+pub fn glob_match(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<_> = pattern.to_lowercase().chars().collect();
+    let value: Vec<_> = value.to_lowercase().chars().collect();
+
+    let (mut p, mut v) = (0, 0);
+    // the last * seen and where in the value its match ends, to retry with a longer match
+    let mut star = None;
+
+    while v < value.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, v));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == value[v] => {
+                p += 1;
+                v += 1;
+            }
+            _ => match star {
+                Some((star_p, star_v)) => {
+                    star = Some((star_p, star_v + 1));
+                    p = star_p + 1;
+                    v = star_v + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// How the index reads the files it finds. Resolving targets and display names takes the
+/// shell, and failures are reported by whoever implements it
+pub trait Loader {
+    fn load_lnk(&self, path: &Path) -> Option<ShellLink>;
+    fn load_url(&self, path: &Path) -> Option<InternetShortcut>;
+    fn resolve(&self, path: &Path, lnk: &ShellLink) -> Option<PathBuf>;
+    fn display_name(&self, path: &Path) -> String;
+}
+
+/// A .lnk or .url file found under one of the roots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    // to the root it was found in
+    pub relative: PathBuf,
+    pub path: PathBuf,
+    // for .url files and links to them
+    pub url: Option<String>,
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    let ext = path.extension().and_then(|x| x.to_str());
+    ext.is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+}
+
+pub fn is_url(path: &Path) -> bool {
+    has_extension(path, "url")
+}
+
+/// The .lnk and .url files under `roots` that pass the filter, without the ones that are in
+/// the same place in another root or start the same thing under the same name
+pub fn index<L: Loader>(roots: &[PathBuf], filter: &EntryFilter, loader: &L) -> Vec<Entry> {
+    let mut vec: Vec<_> = roots
+        .iter()
+        .flat_map(|root| {
+            let iter = RecursiveSearch::new(root);
+            iter.map(move |entry| {
+                let relative = entry.path().strip_prefix(root).unwrap().to_owned();
+                (entry, relative)
+            })
+        })
+        // select only .lnk and .url files, skipping desktop.ini and the like
+        .filter_map(|(entry, relative)| {
+            let path = entry.path();
+            match has_extension(&path, "lnk") || is_url(&path) {
+                true => Some((path, relative)),
+                false => None,
+            }
+        })
+        .filter(|(_, relative)| !filter.excludes_path(relative))
+        // open and parse the files, skipping the ones that are corrupt
+        .filter_map(|(path, relative)| {
+            if is_url(&path) {
+                let url = loader.load_url(&path)?.url;
+                return Some((path, relative, None, None, Some(url)));
+            }
+
+            let lnk = loader.load_lnk(&path)?;
+            let target = loader.resolve(&path, &lnk);
+            let url = match &target {
+                // a broken .url still leaves the link to start it
+                Some(target) if is_url(target) => loader.load_url(target).map(|url| url.url),
+                _ => None,
+            };
+
+            let arguments = lnk.command_line_arguments;
+            Some((path, relative, target, arguments, url))
+        })
+        // select only urls and .lnk files that point to allowed extensions
+        .filter(|(_, _, target, _, url)| match target {
+            _ if url.is_some() => true,
+            None => true, // allow links that couldn't be resolved
+            Some(target) => filter.allows_target(target),
+        })
+        // get display names and add to the tuple
+        .map(|(path, relative, target, arguments, url)| {
+            let name = loader.display_name(&path);
+            (path, relative, target, arguments, url, name)
+        })
+        .filter(|(.., name)| !filter.excludes(name))
+        .collect();
+
+    for i in (0..vec.len()).rev() {
+        // deduplicate .lnk files that either:
+        //   - are in the same relative path within the start menu
+        //   - have the same target, arguments, url and name
+
+        let a = &vec[i];
+        let other = vec[..i]
+            .iter()
+            .any(|b| a.1 == b.1 || (a.2 == b.2 && a.3 == b.3 && a.4 == b.4 && a.5 == b.5));
+
+        if other {
+            vec.remove(i);
+        }
+    }
+
+    vec.into_iter()
+        .map(|(path, relative, _, _, url, name)| Entry {
+            name,
+            relative,
+            path,
+            url,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shell_link::{Environment, Location};
+
+    // reads the files without the shell, the name is the file's
+    struct Files;
+
+    impl Loader for Files {
+        fn load_lnk(&self, path: &Path) -> Option<ShellLink> {
+            ShellLink::load(&std::fs::read(path).ok()?).ok()
+        }
+
+        fn load_url(&self, path: &Path) -> Option<InternetShortcut> {
+            InternetShortcut::load(path).ok()
+        }
+
+        fn resolve(&self, _: &Path, lnk: &ShellLink) -> Option<PathBuf> {
+            match lnk.target(&Environment::current())? {
+                Location::Path(path) => Some(path.replace('\\', "/").into()),
+                _ => None,
+            }
+        }
+
+        fn display_name(&self, path: &Path) -> String {
+            let stem = path.file_stem().map(|stem| stem.to_string_lossy());
+            stem.unwrap_or_default().into_owned()
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("start-menu-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(dir: &Path, relative: &str, data: &[u8]) {
+        let path = dir.join(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, data).unwrap();
+    }
+
+    fn lnk(target: &str) -> Vec<u8> {
+        ShellLink::new(target).store().unwrap()
+    }
+
+    // the defaults of the start menu config
+    fn filter() -> EntryFilter {
+        let exclude = ["chm", "txt", "rtf", "pdf", "html", "ini"];
+        EntryFilter {
+            exclude: exclude.iter().map(|&ext| ext.into()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn names(roots: &[PathBuf], filter: &EntryFilter) -> Vec<(String, String)> {
+        let mut names: Vec<_> = index(roots, filter, &Files)
+            .into_iter()
+            .map(|entry| (entry.name, entry.relative.to_string_lossy().into_owned()))
+            .collect();
+
+        names.sort();
+        names
+    }
+
+    fn start_menu(name: &str) -> PathBuf {
+        let dir = temp_dir(name);
+
+        write(&dir, "Game/Game.lnk", &lnk(r"C:\Games\Game\game.exe"));
+        write(
+            &dir,
+            "Game/Uninstall Game.lnk",
+            &lnk(r"C:\Games\Game\unins000.exe"),
+        );
+        write(&dir, "Game/Manual.lnk", &lnk(r"C:\Games\Game\manual.pdf"));
+        write(&dir, "Game/desktop.ini", b"[.ShellClassInfo]\r\n");
+        write(
+            &dir,
+            "Services.lnk",
+            &lnk(r"C:\Windows\system32\services.msc"),
+        );
+        write(
+            &dir,
+            "Store.url",
+            b"[InternetShortcut]\r\nURL=steam://rungameid/620\r\n",
+        );
+        dir
+    }
+
+    #[test]
+    fn roots_and_extensions() {
+        let dir = start_menu("extensions");
+        let other = temp_dir("extensions-other");
+        // the same place in another root, and the same target under another path
+        write(&other, "Services.lnk", &lnk(r"C:\Windows\regedit.exe"));
+        write(&other, "Tools/Game.lnk", &lnk(r"C:\Games\Game\game.exe"));
+        let roots = [dir.clone(), other.clone()];
+
+        assert_eq!(
+            names(&roots, &filter()),
+            vec![
+                ("Game".into(), "Game/Game.lnk".into()),
+                ("Services".into(), "Services.lnk".into()),
+                ("Store".into(), "Store.url".into()),
+                ("Uninstall Game".into(), "Game/Uninstall Game.lnk".into()),
+            ]
+        );
+
+        // urls have no target to check
+        let filter = EntryFilter {
+            include: vec!["MSC".into(), "pdf".into()],
+            ..Default::default()
+        };
+        let names = names(std::slice::from_ref(&dir), &filter);
+        let names: Vec<_> = names.into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["Manual", "Services", "Store"]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+        std::fs::remove_dir_all(other).unwrap();
+    }
+
+    #[test]
+    fn links_to_urls() {
+        let dir = temp_dir("urls");
+        let store = dir.join("Store.url");
+        write(
+            &dir,
+            "Store.url",
+            b"[InternetShortcut]\r\nURL=steam://rungameid/620\r\n",
+        );
+        write(&dir, "Broken.url", b"not a shortcut");
+        write(&dir, "Links/Store.lnk", &lnk(&store.to_string_lossy()));
+        let broken = dir.join("Broken.url");
+        write(&dir, "Links/Broken.lnk", &lnk(&broken.to_string_lossy()));
+        write(
+            &dir,
+            "Links/Missing.lnk",
+            &lnk(&dir.join("Missing.url").to_string_lossy()),
+        );
+
+        let mut entries: Vec<_> = index(std::slice::from_ref(&dir), &filter(), &Files)
+            .into_iter()
+            .map(|entry| (entry.relative.to_string_lossy().into_owned(), entry.url))
+            .collect();
+        entries.sort();
+
+        let url = Some("steam://rungameid/620".to_owned());
+        assert_eq!(
+            entries,
+            vec![
+                ("Links/Broken.lnk".into(), None),
+                ("Links/Missing.lnk".into(), None),
+                ("Links/Store.lnk".into(), url.clone()),
+                ("Store.url".into(), url),
+            ]
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn exclude_patterns() {
+        let dir = start_menu("exclude");
+
+        // the relative path or the name can match
+        let filter = EntryFilter {
+            patterns: vec!["*uninstall*".into(), "game/g?me.*".into(), "Store".into()],
+            ..filter()
+        };
+        let names = names(std::slice::from_ref(&dir), &filter);
+        let names: Vec<_> = names.into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["Services"]);
+
+        // windows paths have \ between their parts
+        let filter = EntryFilter {
+            patterns: vec!["Games/*".into()],
+            ..Default::default()
+        };
+        assert!(filter.excludes_path(Path::new(r"Games\Game.lnk")));
+        assert!(filter.excludes_path(Path::new("Games/Game.lnk")));
+        assert!(!filter.excludes_path(Path::new("Game.lnk")));
+
+        assert!(glob_match("*Readme*", "Game/README.lnk"));
+        assert!(glob_match("*a*b", "xaxxab"));
+        assert!(!glob_match("*a*b", "xaxxabc"));
+        assert!(!glob_match("?", ""));
+        assert!(glob_match("**", ""));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
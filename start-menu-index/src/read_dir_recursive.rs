@@ -46,7 +46,7 @@ impl Iterator for RecursiveSearch {
             }
 
             if ty.is_dir() {
-                match std::fs::read_dir(&entry.path()) {
+                match std::fs::read_dir(entry.path()) {
                     Ok(iter) => {
                         self.stack.push(iter);
                         continue;
@@ -0,0 +1,125 @@
+use std::{
+    io::{Error, ErrorKind},
+    path::Path,
+};
+
+use shell_link::decode_ansi;
+
+/// The [InternetShortcut] section of a .url file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InternetShortcut {
+    pub url: String,
+    pub icon_file: Option<String>,
+    pub icon_index: i32,
+}
+
+impl InternetShortcut {
+    pub fn parse(src: &str) -> Option<InternetShortcut> {
+        let mut section = "";
+        let mut url = None;
+        let mut icon_file = None;
+        let mut icon_index = 0;
+
+        for line in src.lines().map(str::trim) {
+            if line.starts_with('[') && line.ends_with(']') {
+                section = &line[1..line.len() - 1];
+                continue;
+            }
+
+            if !section.eq_ignore_ascii_case("InternetShortcut") || line.starts_with(';') {
+                continue;
+            }
+
+            let (key, value) = match line.find('=') {
+                Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+                None => continue,
+            };
+
+            match key.to_ascii_lowercase().as_str() {
+                "url" => url = Some(value.to_owned()),
+                "iconfile" => icon_file = Some(value.to_owned()).filter(|v| !v.is_empty()),
+                "iconindex" => icon_index = value.parse().unwrap_or(0),
+                _ => {}
+            }
+        }
+
+        Some(InternetShortcut {
+            url: url.filter(|url| !url.is_empty())?,
+            icon_file,
+            icon_index,
+        })
+    }
+
+    pub fn load(path: &Path) -> std::io::Result<InternetShortcut> {
+        let raw = std::fs::read(path)?;
+        let raw = raw.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&raw);
+
+        // written in the ansi code page, unless something saved it as utf-8
+        let src = match std::str::from_utf8(raw) {
+            Ok(src) => src.to_owned(),
+            Err(_) => decode_ansi(raw),
+        };
+
+        InternetShortcut::parse(&src)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "no URL in [InternetShortcut]"))
+    }
+}
+
+/// The game a steam://rungameid/<id> or steam://run/<id> url starts
+pub fn steam_app_id(url: &str) -> Option<u32> {
+    let id = url.strip_prefix("steam://rungameid/");
+    let id = id.or_else(|| url.strip_prefix("steam://run/"))?;
+
+    // non-steam games have 64 bit ids, which don't parse
+    id.split(['/', '?']).next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steam_url() {
+        let shortcut = InternetShortcut::parse(
+            "[{000214A0-0000-0000-C000-000000000046}]\r\n\
+             Prop3=19,0\r\n\
+             [InternetShortcut]\r\n\
+             IDList=\r\n\
+             IconIndex=0\r\n\
+             URL=steam://rungameid/504230\r\n\
+             IconFile=C:\\Program Files (x86)\\Steam\\steam\\games\\0b8e.ico\r\n",
+        )
+        .unwrap();
+
+        assert_eq!(shortcut.url, "steam://rungameid/504230");
+        assert_eq!(
+            shortcut.icon_file.as_deref(),
+            Some(r"C:\Program Files (x86)\Steam\steam\games\0b8e.ico")
+        );
+        assert_eq!(steam_app_id(&shortcut.url), Some(504230));
+    }
+
+    #[test]
+    fn other_urls() {
+        let epic = InternetShortcut::parse(
+            "[InternetShortcut]\n\
+             url = com.epicgames.launcher://apps/Salt?action=launch&silent=true\n\
+             iconindex=-3\n",
+        )
+        .unwrap();
+
+        assert_eq!(epic.icon_file, None);
+        assert_eq!(epic.icon_index, -3);
+        assert_eq!(steam_app_id(&epic.url), None);
+
+        assert_eq!(steam_app_id("steam://run/620//-novid/"), Some(620));
+        // a non-steam game added to steam
+        assert_eq!(steam_app_id("steam://rungameid/11529127427186868224"), None);
+
+        assert_eq!(
+            InternetShortcut::parse("[Other]\nURL=https://example.com"),
+            None
+        );
+        assert_eq!(InternetShortcut::parse("[InternetShortcut]\nURL="), None);
+    }
+}